@@ -0,0 +1,45 @@
+-- Lists the desktop entries on the desktop itself, such as the game
+-- shortcuts Steam and Lutris create there. Set `dir` in the plugin's config
+-- block to read another directory instead.
+local tmpas = require('tmpas')
+
+function desktop_dir()
+    local home = tmpas.env.get('HOME') or ''
+    -- Without a config directory there is no user-dirs.dirs to read.
+    local config_home = tmpas.xdg.config_home()
+    local raw = config_home and tmpas.fs.read(config_home .. '/user-dirs.dirs')
+    if raw then
+        local dir = string.match(raw, 'XDG_DESKTOP_DIR="([^"]*)"')
+        if dir then
+            return (string.gsub(dir, '^%$HOME', home))
+        end
+    end
+    return home .. '/Desktop'
+end
+
+function getents()
+    local ret = {}
+    local dir = config.dir or desktop_dir()
+    for _, path in ipairs(tmpas.fs.glob(dir .. '/*.desktop') or {}) do
+        local desktop = tmpas.parse.desktop(tmpas.fs.read(path) or '')
+        if desktop and desktop.Name and desktop.Exec then
+            -- Field codes like %U have nothing to expand to here.
+            local exec = string.gsub(desktop.Exec, '%%%a', '')
+            local terms = { 'Desktop' }
+            if desktop.Comment then
+                table.insert(terms, desktop.Comment)
+            end
+            table.insert(ret, entry {
+                name = desktop.Name,
+                exec = exec,
+                search_terms = terms,
+            })
+        end
+    end
+    return ret
+end
+
+plugin {
+    name = "Desktop Shortcuts",
+    entries = getents(),
+}
@@ -1,47 +1,83 @@
 use crate::model::ListEntry;
-use crate::plugins::{BuiltinPlugins, LoadablePlugins};
+use crate::plugins::{
+    BrowserConfig, BuiltinPlugins, EmojiConfig, FilesConfig, LoadablePlugins, RetroArchConfig,
+    SearchEngine, SessionConfig,
+};
+use crate::supervisor::PluginBudget;
+use crate::utils::ok_or_log;
 
 use serde::{Deserialize, Serialize};
 
 use std::collections::HashMap;
 use std::fmt;
+use std::fs;
+use std::path::PathBuf;
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
     #[serde(default)]
     pub terminal: Option<String>,
     #[serde(default)]
     pub language: Option<String>,
+    /// Launch entries in the background and keep the launcher open afterwards.
+    #[serde(default)]
+    pub keep_open: bool,
     #[serde(default, rename = "plugins")]
     pub builtin_plugins: Vec<BuiltinPlugins>,
 
     #[serde(default, rename = "plugin")]
     pub loaded_plugins: Vec<LoadablePlugins>,
+    #[serde(default)]
+    pub plugin_budget: PluginBudget,
+
+    #[serde(default)]
+    pub files: FilesConfig,
+    #[serde(default)]
+    pub browser: BrowserConfig,
+    #[serde(default)]
+    pub emoji: EmojiConfig,
+    #[serde(default)]
+    pub session: SessionConfig,
+    #[serde(default, rename = "search_engine")]
+    pub search_engines: Vec<SearchEngine>,
+    #[serde(default)]
+    pub retroarch: RetroArchConfig,
 
     #[serde(default, alias = "ui")]
     pub interfaces: HashMap<UiTag, UiConfig>,
 }
 
 impl Config {
-    pub fn make_terminal_command(&self, entry: &ListEntry) -> String {
+    /// Builds the arguments used to run an entry inside the configured
+    /// terminal. A bare `$COMMAND` or `$FLAGS` word expands into separate
+    /// arguments so that commands containing spaces survive intact.
+    pub fn make_terminal_argv(&self, entry: &ListEntry) -> Vec<String> {
         let binary = entry.exec_name().unwrap();
-        let flags = entry
-            .exec_command
-            .iter()
-            .skip(1)
-            .fold(String::new(), |acc, cur| format!("{} {}", acc, cur));
-        let command = format!("{} {}", binary, flags);
+        let flags = &entry.exec_command[1..];
+        let joined_flags = flags.join(" ");
+        let command = entry.exec_command.join(" ");
         let subs = [
             ("$DISPLAY_NAME", entry.name()),
             ("$BINARY", binary),
-            ("$FLAGS", &flags),
+            ("$FLAGS", &joined_flags),
             ("$COMMAND", &command),
         ];
-        let mut raw = self.terminal.as_deref().unwrap_or("$COMMAND").to_owned();
-        for (k, v) in &subs {
-            raw = raw.replace(k, v);
+        let template = self.terminal.as_deref().unwrap_or("$COMMAND");
+        let mut retvl = Vec::new();
+        for word in template.split_whitespace() {
+            match word {
+                "$COMMAND" => retvl.extend(entry.exec_command.iter().cloned()),
+                "$FLAGS" => retvl.extend(flags.iter().cloned()),
+                _ => {
+                    let mut raw = word.to_owned();
+                    for (k, v) in &subs {
+                        raw = raw.replace(k, v);
+                    }
+                    retvl.push(raw);
+                }
+            }
         }
-        raw
+        retvl
     }
 
     pub fn is_interface_enabled(&self, tag: UiTag) -> bool {
@@ -91,6 +127,35 @@ impl Config {
     }
 }
 
+/// Where the config comes from, so that it can be read again when the file
+/// changes.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSource {
+    pub path: Option<PathBuf>,
+    /// Set by `--keep-open`, which wins over the file.
+    pub keep_open: bool,
+}
+
+impl ConfigSource {
+    /// Reads and parses the config file, logging what went wrong if it
+    /// cannot. Without a file this is the default config.
+    pub fn load(&self) -> Option<Config> {
+        let mut config = match self.path.as_ref() {
+            Some(path) => {
+                let raw = ok_or_log(fs::read_to_string(path), |e| {
+                    eprintln!("Error reading config: {}", e)
+                })?;
+                ok_or_log(toml::de::from_str(&raw), |e| {
+                    eprintln!("Error parsing config: {}", e)
+                })?
+            }
+            None => Config::default(),
+        };
+        config.keep_open |= self.keep_open;
+        Some(config)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
 #[serde(default)]
 pub struct UiConfig {
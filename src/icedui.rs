@@ -1,4 +1,5 @@
 use crate::model::{entry_tree_get, entry_tree_with_paths, EntryPath, ListEntry};
+use crate::state::{RunResult, POLL_INTERVAL};
 use crate::{AppMessage, State};
 
 use iced::window;
@@ -12,18 +13,22 @@ use iced::{
     VerticalAlignment,
 };
 use iced_futures::executor::Executor;
+use iced_futures::BoxStream;
 use iced_native::keyboard::Event as KeyboardEvent;
 use iced_native::keyboard::KeyCode;
+use iced_native::subscription::Recipe;
 use iced_native::Event;
 
-use futures::FutureExt;
+use futures::{FutureExt, StreamExt};
 
 use std::borrow::Cow;
 use std::future::Future;
+use std::hash::{Hash, Hasher};
 use std::pin::Pin;
 use std::sync::mpsc;
 use std::task::Poll;
 use std::thread;
+use std::time::{Duration, Instant};
 
 pub fn run(state: State) {
     let mut settings = Settings::with_flags(state);
@@ -101,12 +106,17 @@ pub enum Message {
     CursorUp,
     CursorDown,
     RunSelected,
+    ShowActions,
+    HideActions,
+    Tick,
 }
 
 pub struct IcedUi {
     app_state: super::State,
     search_buffer: SearchBuffer,
     entry_list: EntryList,
+    status: Option<String>,
+    showing_actions: bool,
 }
 
 impl Application for IcedUi {
@@ -119,10 +129,13 @@ impl Application for IcedUi {
         let mut entry_list = EntryList::new();
         let entries = app_state.search("", 1024);
         entry_list.set_results(entries);
+        let status = app_state.take_warning();
         let res = Self {
             app_state,
             search_buffer,
             entry_list,
+            status,
+            showing_actions: false,
         };
         (res, Command::none())
     }
@@ -130,12 +143,23 @@ impl Application for IcedUi {
         "TMPAS Application Runner".into()
     }
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
-        match message {
+        let command = match message {
             Message::Backend(AppMessage::SearchResults(results)) => {
                 self.entry_list.set_results(results);
                 Command::none()
             }
+            Message::Backend(AppMessage::Actions(actions)) => {
+                self.entry_list.set_results(actions);
+                self.showing_actions = true;
+                Command::none()
+            }
+            Message::Backend(AppMessage::Status(msg)) => {
+                self.status = Some(msg);
+                Command::none()
+            }
             Message::SetBuffer(buf) => {
+                self.status = None;
+                self.showing_actions = false;
                 self.search_buffer.buffer = buf;
                 let new_res = self.app_state.search(&self.search_buffer.buffer, 1024);
                 self.entry_list.set_results(new_res);
@@ -150,8 +174,45 @@ impl Application for IcedUi {
                 Command::none()
             }
             Message::RunSelected => {
-                if let Some(ent) = self.entry_list.selected() {
-                    self.app_state.run(ent);
+                if let Some(ent) = self.entry_list.selected().cloned() {
+                    match self.app_state.run(&ent) {
+                        RunResult::Nothing => {}
+                        RunResult::Status(msg) => {
+                            self.status = Some(msg);
+                        }
+                        RunResult::Exec(ent) => {
+                            self.app_state.exec(&ent);
+                        }
+                        RunResult::Entries(entries) => {
+                            self.status = Some(format!("{} (Esc to go back)", ent.name()));
+                            return self.update(Message::Backend(AppMessage::Actions(entries)));
+                        }
+                    }
+                }
+                Command::none()
+            }
+            Message::ShowActions => {
+                if let Some(ent) = self.entry_list.selected().cloned() {
+                    let actions = self.app_state.actions(&ent);
+                    self.status = Some(format!("Actions for {} (Esc to go back)", ent.name()));
+                    self.update(Message::Backend(AppMessage::Actions(actions)))
+                } else {
+                    Command::none()
+                }
+            }
+            Message::Tick => {
+                if self.app_state.poll() && !self.showing_actions {
+                    let new_res = self.app_state.search(&self.search_buffer.buffer, 1024);
+                    self.entry_list.set_results(new_res);
+                }
+                Command::none()
+            }
+            Message::HideActions => {
+                if self.showing_actions {
+                    self.showing_actions = false;
+                    self.status = None;
+                    let new_res = self.app_state.search(&self.search_buffer.buffer, 1024);
+                    self.entry_list.set_results(new_res);
                 }
                 Command::none()
             }
@@ -159,7 +220,11 @@ impl Application for IcedUi {
             _ => {
                 unreachable!()
             }
+        };
+        if let Some(warning) = self.app_state.take_warning() {
+            self.status = Some(warning);
         }
+        command
     }
 
     fn mode(&self) -> window::Mode {
@@ -167,10 +232,13 @@ impl Application for IcedUi {
     }
 
     fn view(&mut self) -> Element<'_, Self::Message> {
-        let elm = Column::new()
+        let mut elm = Column::new()
             .push(self.search_buffer.display())
             .push(self.entry_list.display())
             .align_items(Align::Start);
+        if let Some(status) = self.status.as_deref() {
+            elm = elm.push(Container::new(Text::new(status)).padding(16));
+        }
         let elm = Container::new(elm).style(StyleWrapper(container::Style {
             background: Some(Background::Color(Color::TRANSPARENT)),
             ..Default::default()
@@ -178,7 +246,8 @@ impl Application for IcedUi {
         elm.into()
     }
     fn subscription(&self) -> Subscription<Self::Message> {
-        iced_native::subscription::events_with(|evt, _| match evt {
+        let ticks = Subscription::from_recipe(Ticks(POLL_INTERVAL)).map(|_| Message::Tick);
+        let keys = iced_native::subscription::events_with(|evt, _| match evt {
             Event::Keyboard(KeyboardEvent::KeyPressed {
                 key_code: KeyCode::Up,
                 ..
@@ -187,12 +256,54 @@ impl Application for IcedUi {
                 key_code: KeyCode::Down,
                 ..
             }) => Some(Message::CursorDown),
+            Event::Keyboard(KeyboardEvent::KeyPressed {
+                key_code: KeyCode::Enter,
+                modifiers,
+            }) if modifiers.shift => Some(Message::ShowActions),
             Event::Keyboard(KeyboardEvent::KeyPressed {
                 key_code: KeyCode::Enter,
                 ..
             }) => Some(Message::RunSelected),
+            Event::Keyboard(KeyboardEvent::KeyPressed {
+                key_code: KeyCode::Tab,
+                ..
+            }) => Some(Message::ShowActions),
+            Event::Keyboard(KeyboardEvent::KeyPressed {
+                key_code: KeyCode::Escape,
+                ..
+            }) => Some(Message::HideActions),
             _ => None,
+        });
+        Subscription::batch(vec![keys, ticks])
+    }
+}
+
+/// Fires every so often, so that reloads and entries plugins finish in the
+/// background show up without a key press.
+struct Ticks(Duration);
+
+impl<H: Hasher, E> Recipe<H, E> for Ticks {
+    type Output = Instant;
+
+    fn hash(&self, state: &mut H) {
+        std::any::TypeId::of::<Self>().hash(state);
+        self.0.hash(state);
+    }
+
+    fn stream(self: Box<Self>, _input: BoxStream<E>) -> BoxStream<Instant> {
+        let interval = self.0;
+        futures::stream::unfold(Instant::now() + interval, move |due| {
+            // `IcedUiExecutor` polls every pending future every few
+            // milliseconds, so this needs no waker.
+            futures::future::poll_fn(move |_| {
+                if Instant::now() >= due {
+                    Poll::Ready(Some((due, due + interval)))
+                } else {
+                    Poll::Pending
+                }
+            })
         })
+        .boxed()
     }
 }
 
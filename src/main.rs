@@ -6,8 +6,12 @@ use model::ListEntry;
 mod state;
 use state::State;
 
+mod supervisor;
+
+mod watcher;
+
 mod config;
-use config::{Config, UiTag};
+use config::{Config, ConfigSource, UiTag};
 
 #[cfg(feature = "crossterm-ui")]
 mod tui;
@@ -19,14 +23,17 @@ mod icedui;
 mod smithayui;
 
 mod plugins;
+use plugins::PluginTest;
 
 use structopt::StructOpt;
-use utils::ok_or_log;
 
 use std::path::PathBuf;
 
 fn main() {
     let args = CmdArgs::from_args();
+    if let Some(Command::PluginTest(test)) = &args.command {
+        std::process::exit(test.run());
+    }
     if args.verify {
         let path = args
             .config
@@ -36,23 +43,18 @@ fn main() {
         println!("{:?}", parsed);
         return;
     }
-    let config: Config = args
-        .config
-        .as_ref()
-        .and_then(|pt| {
-            ok_or_log(std::fs::read_to_string(pt), |e| {
-                eprintln!("Error reading config: {}", e)
-            })
-        })
-        .and_then(|st| {
-            ok_or_log(toml::de::from_str(&st), |e| {
-                eprintln!("Error parsing config: {}", e)
-            })
-        })
-        .unwrap_or_default();
+    let source = ConfigSource {
+        path: args.config.clone(),
+        keep_open: args.keep_open,
+    };
+    let config = source.load().unwrap_or_else(|| Config {
+        keep_open: source.keep_open,
+        ..Config::default()
+    });
     eprintln!("CONFIG: {:?}", config);
     let mut state = State::new(config);
     state.start();
+    state.watch(source);
 
     if args.tui && args.gui {
         panic!("Can't run both the tui and gui at the same time.");
@@ -94,6 +96,9 @@ fn main() {
 pub enum UiMessage {
     DoSearch(String),
     RunEntry(ListEntry),
+    ShowActions(ListEntry),
+    /// Nothing happened for `POLL_INTERVAL`.
+    Idle,
     Quit,
 }
 
@@ -101,6 +106,8 @@ pub enum UiMessage {
 #[derive(Debug, Clone)]
 pub enum AppMessage {
     SearchResults(Vec<ListEntry>),
+    Actions(Vec<ListEntry>),
+    Status(String),
 }
 
 #[derive(Debug, StructOpt)]
@@ -115,4 +122,15 @@ struct CmdArgs {
     gui: bool,
     #[structopt(long)]
     verify: bool,
+    /// Keep the launcher open after running an entry
+    #[structopt(long)]
+    keep_open: bool,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Load a Lua plugin on its own and print every entry it produces
+    PluginTest(PluginTest),
 }
@@ -3,12 +3,65 @@ use crate::config::Config;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::ops::{Add, AddAssign};
-use std::{cmp::Ordering, path::Path};
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::task::Poll;
 
 pub trait EntryPlugin {
     fn name(&self) -> String;
     fn start(&mut self, config: &Config);
     fn next(&mut self) -> Option<ListEntry>;
+
+    /// Like `next`, but a plugin that is still working on its next entry can
+    /// return `Poll::Pending` instead of blocking. The launcher then loads
+    /// entries from other plugins and asks again later.
+    fn poll_next(&mut self) -> Poll<Option<ListEntry>> {
+        Poll::Ready(self.next())
+    }
+
+    /// Entries generated from the text currently typed in the search bar.
+    /// Called on every search and ranked together with the loaded entries.
+    fn query(&mut self, _text: &str) -> Vec<ListEntry> {
+        Vec::new()
+    }
+
+    /// Whether `query` always answers the same text with the same entries,
+    /// so that the launcher may reuse them instead of asking again. Plugins
+    /// whose entries go stale on their own should return `false`.
+    fn caches_queries(&self) -> bool {
+        true
+    }
+
+    /// Extra entries to show in the actions menu of `entry`, after the
+    /// built-in actions. Called for every entry, not just this plugin's own.
+    fn actions(&mut self, _entry: &ListEntry) -> Vec<ListEntry> {
+        Vec::new()
+    }
+
+    /// Entries to show for the text in the search bar when nothing else
+    /// matches it at all.
+    fn fallback(&mut self, _text: &str) -> Vec<ListEntry> {
+        Vec::new()
+    }
+
+    /// Runs an entry flagged with `RunFlags::plugin_handled` in place of its
+    /// command. Every plugin is asked in turn; return `None` for entries that
+    /// are not this plugin's own.
+    fn select(&mut self, _entry: &ListEntry) -> Option<Selected> {
+        None
+    }
+}
+
+/// What a plugin did with one of its own entries in `EntryPlugin::select`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selected {
+    /// The plugin handled the entry itself; the message is shown as a status.
+    Done(String),
+    /// The plugin wants this entry run in its place.
+    Run(ListEntry),
+    /// The plugin wants these entries shown as a menu to pick from.
+    Entries(Vec<ListEntry>),
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
@@ -18,6 +71,19 @@ pub struct ListEntry {
     pub exec_command: Vec<String>,
     pub exec_flags: RunFlags,
     pub children: Vec<ListEntry>,
+    /// The file this entry was read from, if any.
+    pub source: Option<PathBuf>,
+    /// Set by plugins that need to tell their entries apart in `select` or
+    /// `actions` even when every other field is equal. Never shown.
+    pub tag: Option<u64>,
+}
+
+static NEXT_TAG: AtomicU64 = AtomicU64::new(1);
+
+/// Hands out a fresh `ListEntry::tag`. Shared between plugins so that one
+/// plugin never claims another's entry in `select`.
+pub fn next_tag() -> u64 {
+    NEXT_TAG.fetch_add(1, AtomicOrdering::Relaxed)
 }
 
 impl ListEntry {
@@ -33,6 +99,14 @@ impl ListEntry {
         let stripped = as_path.file_name().and_then(|s| s.to_str());
         Some(stripped.unwrap_or(raw))
     }
+    /// Whether selecting the entry does anything, either by running its
+    /// command or by handing it to its plugin.
+    pub fn is_runnable(&self) -> bool {
+        self.has_command() || self.exec_flags.plugin_handled()
+    }
+    pub fn has_command(&self) -> bool {
+        self.exec_command.first().is_some_and(|cmd| !cmd.is_empty())
+    }
 }
 
 pub fn entry_tree_with_paths(
@@ -74,6 +148,9 @@ pub struct RunFlags(u16);
 impl RunFlags {
     const IS_TERM: RunFlags = RunFlags(0x1);
     const SHOULD_FORK: RunFlags = RunFlags(0x2);
+    const KEEP_OPEN: RunFlags = RunFlags(0x4);
+    const PLUGIN_HANDLED: RunFlags = RunFlags(0x8);
+    const CONFIRM: RunFlags = RunFlags(0x10);
 
     pub fn new() -> Self {
         Self(0)
@@ -114,6 +191,59 @@ impl RunFlags {
         self.set_should_fork(value);
         self
     }
+
+    pub fn keep_open(self) -> bool {
+        self.0 & Self::KEEP_OPEN.0 != 0
+    }
+
+    pub fn set_keep_open(&mut self, value: bool) {
+        if value {
+            self.0 |= Self::KEEP_OPEN.0;
+        } else {
+            self.0 &= !Self::KEEP_OPEN.0;
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_keep_open(mut self, value: bool) -> Self {
+        self.set_keep_open(value);
+        self
+    }
+
+    pub fn plugin_handled(self) -> bool {
+        self.0 & Self::PLUGIN_HANDLED.0 != 0
+    }
+
+    pub fn set_plugin_handled(&mut self, value: bool) {
+        if value {
+            self.0 |= Self::PLUGIN_HANDLED.0;
+        } else {
+            self.0 &= !Self::PLUGIN_HANDLED.0;
+        }
+    }
+
+    pub fn with_plugin_handled(mut self, value: bool) -> Self {
+        self.set_plugin_handled(value);
+        self
+    }
+
+    /// Whether the user has to select the entry a second time before it runs.
+    pub fn needs_confirm(self) -> bool {
+        self.0 & Self::CONFIRM.0 != 0
+    }
+
+    pub fn set_confirm(&mut self, value: bool) {
+        if value {
+            self.0 |= Self::CONFIRM.0;
+        } else {
+            self.0 &= !Self::CONFIRM.0;
+        }
+    }
+
+    pub fn with_confirm(mut self, value: bool) -> Self {
+        self.set_confirm(value);
+        self
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -1,7 +1,12 @@
 
 mod builtins;
 
-pub use builtins::BuiltinPlugins;
+pub use builtins::{
+    BrowserConfig, BuiltinPlugins, EmojiConfig, FilesConfig, RetroArchConfig, SearchEngine,
+    SessionConfig,
+};
 mod loadable;
 
-pub use loadable::LoadablePlugins;
+pub use loadable::{LoadablePlugins, PluginTest};
+#[cfg(all(test, feature = "plugin-lua"))]
+pub use loadable::lua_config;
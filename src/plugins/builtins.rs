@@ -1,8 +1,44 @@
+mod browser;
+mod calculator;
+mod emoji;
+mod files;
 mod freedesktop;
+mod mimeapps;
+mod pass;
+mod procs;
 mod rawpath;
+mod recent;
+mod retroarch;
+mod session;
+mod ssh;
+mod steam;
+mod units;
+mod websearch;
 
+use browser::BrowserPlugin;
+use calculator::CalculatorPlugin;
+use emoji::EmojiPlugin;
+use files::FilesPlugin;
 use freedesktop::FreedesktopPlugin;
+use pass::PassPlugin;
+use procs::ProcsPlugin;
 use rawpath::RawPathPlugin;
+use recent::RecentPlugin;
+use retroarch::RetroArchPlugin;
+use session::SessionPlugin;
+use ssh::SshPlugin;
+use steam::SteamPlugin;
+use units::UnitsPlugin;
+use websearch::WebSearchPlugin;
+
+pub use browser::BrowserConfig;
+#[cfg(feature = "plugin-lua")]
+pub use freedesktop::{parse_sections, xdg_config_home, xdg_data_dirs, xdg_data_homes, Section};
+pub use emoji::EmojiConfig;
+pub use files::FilesConfig;
+pub use retroarch::RetroArchConfig;
+pub use session::SessionConfig;
+pub use websearch::SearchEngine;
 
 use crate::model::EntryPlugin;
 
@@ -14,6 +50,32 @@ pub enum BuiltinPlugins {
     Freedesktop,
     #[serde(rename = "path")]
     RawPath,
+    #[serde(rename = "calc")]
+    Calculator,
+    #[serde(rename = "units")]
+    Units,
+    #[serde(rename = "files")]
+    Files,
+    #[serde(rename = "ssh")]
+    Ssh,
+    #[serde(rename = "recent")]
+    Recent,
+    #[serde(rename = "browser")]
+    Browser,
+    #[serde(rename = "emoji")]
+    Emoji,
+    #[serde(rename = "procs")]
+    Procs,
+    #[serde(rename = "session")]
+    Session,
+    #[serde(rename = "pass")]
+    Pass,
+    #[serde(rename = "websearch")]
+    WebSearch,
+    #[serde(rename = "steam")]
+    Steam,
+    #[serde(rename = "retroarch")]
+    RetroArch,
 }
 
 impl BuiltinPlugins {
@@ -21,6 +83,19 @@ impl BuiltinPlugins {
         match self {
             BuiltinPlugins::RawPath => Box::new(RawPathPlugin::new()),
             BuiltinPlugins::Freedesktop => Box::new(FreedesktopPlugin::new()),
+            BuiltinPlugins::Calculator => Box::new(CalculatorPlugin::new()),
+            BuiltinPlugins::Units => Box::new(UnitsPlugin::new()),
+            BuiltinPlugins::Files => Box::new(FilesPlugin::new()),
+            BuiltinPlugins::Ssh => Box::new(SshPlugin::new()),
+            BuiltinPlugins::Recent => Box::new(RecentPlugin::new()),
+            BuiltinPlugins::Browser => Box::new(BrowserPlugin::new()),
+            BuiltinPlugins::Emoji => Box::new(EmojiPlugin::new()),
+            BuiltinPlugins::Procs => Box::new(ProcsPlugin::new()),
+            BuiltinPlugins::Session => Box::new(SessionPlugin::new()),
+            BuiltinPlugins::Pass => Box::new(PassPlugin::new()),
+            BuiltinPlugins::WebSearch => Box::new(WebSearchPlugin::new()),
+            BuiltinPlugins::Steam => Box::new(SteamPlugin::new()),
+            BuiltinPlugins::RetroArch => Box::new(RetroArchPlugin::new()),
         }
     }
 }
@@ -0,0 +1,550 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Context, Error};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::model::{EntryPlugin, ListEntry};
+use crate::plugins::builtins::freedesktop::read_sections;
+use crate::plugins::builtins::mimeapps::MimeApps;
+use crate::utils::expand_tilde;
+
+/// The `[browser]` table of the config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BrowserConfig {
+    /// Directories containing a Firefox `profiles.ini`.
+    pub firefox_roots: Vec<String>,
+    /// Chromium-style user data directories, each holding `Default` and
+    /// `Profile *` directories.
+    pub chromium_roots: Vec<String>,
+    /// How many of the most visited history entries to include per profile.
+    pub history_limit: usize,
+}
+
+impl Default for BrowserConfig {
+    fn default() -> Self {
+        let strings = |raw: &[&str]| raw.iter().map(|s| s.to_string()).collect();
+        Self {
+            firefox_roots: strings(&[
+                "~/.mozilla/firefox",
+                "~/.var/app/org.mozilla.firefox/.mozilla/firefox",
+                "~/snap/firefox/common/.mozilla/firefox",
+                "~/.librewolf",
+            ]),
+            chromium_roots: strings(&[
+                "~/.config/chromium",
+                "~/.config/google-chrome",
+                "~/.config/BraveSoftware/Brave-Browser",
+                "~/.config/microsoft-edge",
+                "~/.config/vivaldi",
+                "~/.var/app/org.chromium.Chromium/config/chromium",
+                "~/.var/app/com.google.Chrome/config/google-chrome",
+            ]),
+            history_limit: 100,
+        }
+    }
+}
+
+pub struct BrowserPlugin {
+    inner: Box<dyn Iterator<Item = ListEntry>>,
+}
+
+impl BrowserPlugin {
+    pub fn new() -> Self {
+        Self {
+            inner: Box::new(None.into_iter()),
+        }
+    }
+}
+
+impl EntryPlugin for BrowserPlugin {
+    fn name(&self) -> String {
+        "Browser Bookmarks".to_owned()
+    }
+    fn start(&mut self, config: &Config) {
+        let config = &config.browser;
+        let mut bookmarks = Vec::new();
+        let mut history = Vec::new();
+        let firefox = config
+            .firefox_roots
+            .iter()
+            .flat_map(|root| firefox_profiles(&expand_tilde(root)));
+        for profile in firefox {
+            let res = read_firefox(&profile, config.history_limit);
+            match res.with_context(|| format!("Reading profile {}", profile.display())) {
+                Ok((marks, hist)) => {
+                    bookmarks.extend(marks);
+                    history.extend(hist);
+                }
+                Err(e) => eprintln!("ERROR from browser: {:?}", e),
+            }
+        }
+        let chromium = config
+            .chromium_roots
+            .iter()
+            .flat_map(|root| chromium_profiles(&expand_tilde(root)));
+        for profile in chromium {
+            match read_chromium_bookmarks(&profile) {
+                Ok(marks) => bookmarks.extend(marks),
+                Err(e) => eprintln!("ERROR from browser: {:?}", e),
+            }
+            match read_chromium_history(&profile, config.history_limit) {
+                Ok(hist) => history.extend(hist),
+                Err(e) => eprintln!("ERROR from browser: {:?}", e),
+            }
+        }
+
+        let mime = MimeApps::load();
+        let entries = merge(bookmarks, history)
+            .into_iter()
+            .map(|page| page.into_entry(&mime))
+            .collect::<Vec<_>>();
+        self.inner = Box::new(entries.into_iter());
+    }
+    fn next(&mut self) -> Option<ListEntry> {
+        self.inner.next()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Page {
+    title: String,
+    url: String,
+    /// The folders leading to a bookmark, like `Bookmarks Toolbar/Rust`.
+    /// History entries have none.
+    folder: Option<String>,
+}
+
+impl Page {
+    fn into_entry(self, mime: &MimeApps) -> ListEntry {
+        let (exec_command, exec_flags) = mime.open_url(&self.url);
+        let mut search_terms = vec![self.url.clone()];
+        match self.folder {
+            Some(folder) if !folder.is_empty() => search_terms.push(folder),
+            Some(_) => {}
+            None => search_terms.push("history".to_owned()),
+        }
+        let display_name = if self.title.is_empty() {
+            self.url
+        } else {
+            self.title
+        };
+        ListEntry {
+            display_name: Some(display_name),
+            search_terms,
+            exec_command,
+            exec_flags,
+            children: Vec::new(),
+            source: None,
+            tag: None,
+        }
+    }
+}
+
+/// Drops duplicate bookmarks and any history entry that is also bookmarked.
+fn merge(bookmarks: Vec<Page>, history: Vec<Page>) -> Vec<Page> {
+    let mut seen = HashSet::new();
+    let mut retvl = Vec::new();
+    for page in bookmarks.into_iter().chain(history) {
+        if seen.insert(page.url.clone()) {
+            retvl.push(page);
+        }
+    }
+    retvl
+}
+
+fn is_web_url(url: &str) -> bool {
+    !url.starts_with("place:") && !url.starts_with("javascript:")
+}
+
+/// Reads the profile directories listed in a Firefox `profiles.ini`.
+fn firefox_profiles(root: &Path) -> Vec<PathBuf> {
+    let ini = root.join("profiles.ini");
+    if !ini.is_file() {
+        return Vec::new();
+    }
+    let sections = match read_sections(&ini) {
+        Ok(sections) => sections,
+        Err(e) => {
+            eprintln!("ERROR: Could not read {}: {}", ini.display(), e);
+            return Vec::new();
+        }
+    };
+    sections
+        .iter()
+        .filter(|section| section.header.starts_with("Profile"))
+        .filter_map(|section| {
+            let path = section.get_field("Path")?;
+            let is_relative = section.get_field("IsRelative") != Some("0");
+            let path = if is_relative {
+                root.join(path)
+            } else {
+                PathBuf::from(path)
+            };
+            Some(path).filter(|path| path.join("places.sqlite").is_file())
+        })
+        .collect()
+}
+
+fn chromium_profiles(root: &Path) -> Vec<PathBuf> {
+    let read = match fs::read_dir(root) {
+        Ok(read) => read,
+        Err(_) => {
+            return Vec::new();
+        }
+    };
+    let mut retvl: Vec<PathBuf> = read
+        .filter_map(|ent| Some(ent.ok()?.path()))
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name == "Default" || name.starts_with("Profile ")
+        })
+        .filter(|path| path.is_dir())
+        .collect();
+    retvl.sort();
+    retvl
+}
+
+/// A copy of a database that may be locked by a running browser, removed
+/// again once dropped.
+struct DatabaseCopy {
+    path: PathBuf,
+}
+
+impl DatabaseCopy {
+    fn new(original: &Path) -> Result<Self, Error> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let name = format!(
+            "tmpas-{}-{}.sqlite",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = env::temp_dir().join(name);
+        let retvl = Self { path };
+        fs::copy(original, &retvl.path)
+            .with_context(|| format!("Copying {}", original.display()))?;
+        // Recent changes may still be sitting in the write-ahead log.
+        let wal = with_suffix(original, "-wal");
+        if wal.is_file() {
+            fs::copy(&wal, with_suffix(&retvl.path, "-wal"))?;
+        }
+        Ok(retvl)
+    }
+    fn open(&self) -> Result<Connection, Error> {
+        Connection::open(&self.path).with_context(|| format!("Opening {}", self.path.display()))
+    }
+}
+
+impl Drop for DatabaseCopy {
+    fn drop(&mut self) {
+        for suffix in &["", "-wal", "-shm", "-journal"] {
+            let _ = fs::remove_file(with_suffix(&self.path, suffix));
+        }
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut raw = path.as_os_str().to_owned();
+    raw.push(suffix);
+    PathBuf::from(raw)
+}
+
+fn read_firefox(profile: &Path, history_limit: usize) -> Result<(Vec<Page>, Vec<Page>), Error> {
+    let copy = DatabaseCopy::new(&profile.join("places.sqlite"))?;
+    let conn = copy.open()?;
+
+    struct Node {
+        parent: i64,
+        title: String,
+        guid: String,
+    }
+    let mut folders = HashMap::new();
+    let mut marks = Vec::new();
+    {
+        let mut stmt = conn.prepare(
+            "SELECT b.id, b.parent, b.type, IFNULL(b.title, ''), b.guid, p.url
+             FROM moz_bookmarks b LEFT JOIN moz_places p ON b.fk = p.id
+             ORDER BY b.parent, b.position",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            let parent: i64 = row.get(1)?;
+            let kind: i64 = row.get(2)?;
+            let title: String = row.get(3)?;
+            let guid: String = row.get(4)?;
+            let url: Option<String> = row.get(5)?;
+            match (kind, url) {
+                (1, Some(url)) if is_web_url(&url) => marks.push((parent, title, url)),
+                (2, _) => {
+                    folders.insert(
+                        id,
+                        Node {
+                            parent,
+                            title,
+                            guid,
+                        },
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+    let folder_path = |mut id: i64| -> Option<String> {
+        let mut parts = Vec::new();
+        // A corrupt database can have folders that are their own ancestors.
+        let mut visited = HashSet::new();
+        while let Some(node) = folders.get(&id).filter(|_| visited.insert(id)) {
+            // Tags are stored as folders but are not where a bookmark lives.
+            if node.guid == "tagsfolder_____" {
+                return None;
+            }
+            if node.guid != "root________" && !node.title.is_empty() {
+                parts.push(node.title.as_str());
+            }
+            id = node.parent;
+        }
+        parts.reverse();
+        Some(parts.join("/"))
+    };
+    let bookmarks = marks
+        .into_iter()
+        .filter_map(|(parent, title, url)| {
+            Some(Page {
+                folder: Some(folder_path(parent)?),
+                title,
+                url,
+            })
+        })
+        .collect();
+
+    let mut stmt = conn.prepare(
+        "SELECT url, IFNULL(title, '') FROM moz_places
+         WHERE hidden = 0 AND visit_count > 0
+         ORDER BY frecency DESC LIMIT ?1",
+    )?;
+    let history = stmt
+        .query_map(params![history_limit as i64], |row| {
+            Ok(Page {
+                url: row.get(0)?,
+                title: row.get(1)?,
+                folder: None,
+            })
+        })?
+        .filter_map(|res| res.ok())
+        .filter(|page| is_web_url(&page.url))
+        .collect();
+    Ok((bookmarks, history))
+}
+
+#[derive(Deserialize)]
+struct ChromiumBookmarks {
+    roots: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ChromiumNode {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    children: Vec<ChromiumNode>,
+}
+
+fn read_chromium_bookmarks(profile: &Path) -> Result<Vec<Page>, Error> {
+    let path = profile.join("Bookmarks");
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let raw = fs::read_to_string(&path).with_context(|| format!("Reading {}", path.display()))?;
+    let parsed: ChromiumBookmarks =
+        serde_json::from_str(&raw).with_context(|| format!("Parsing {}", path.display()))?;
+
+    fn walk(node: &ChromiumNode, folder: &str, retvl: &mut Vec<Page>) {
+        match (node.kind.as_str(), node.url.as_ref()) {
+            ("url", Some(url)) if is_web_url(url) => retvl.push(Page {
+                title: node.name.clone(),
+                url: url.clone(),
+                folder: Some(folder.to_owned()),
+            }),
+            ("folder", _) => {
+                let folder = if folder.is_empty() {
+                    node.name.clone()
+                } else {
+                    format!("{}/{}", folder, node.name)
+                };
+                for child in node.children.iter() {
+                    walk(child, &folder, retvl);
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut roots: Vec<_> = parsed.roots.into_iter().collect();
+    roots.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut retvl = Vec::new();
+    for (_, root) in roots {
+        // `roots` also holds bookkeeping values that are not folders.
+        if let Ok(node) = serde_json::from_value::<ChromiumNode>(root) {
+            walk(&node, "", &mut retvl);
+        }
+    }
+    Ok(retvl)
+}
+
+fn read_chromium_history(profile: &Path, limit: usize) -> Result<Vec<Page>, Error> {
+    let path = profile.join("History");
+    if limit == 0 || !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let copy = DatabaseCopy::new(&path)?;
+    let conn = copy.open()?;
+    let mut stmt = conn.prepare(
+        "SELECT url, title FROM urls WHERE hidden = 0
+         ORDER BY visit_count DESC, last_visit_time DESC LIMIT ?1",
+    )?;
+    let retvl = stmt
+        .query_map(params![limit as i64], |row| {
+            Ok(Page {
+                url: row.get(0)?,
+                title: row.get(1)?,
+                folder: None,
+            })
+        })?
+        .filter_map(|res| res.ok())
+        .filter(|page| is_web_url(&page.url))
+        .collect();
+    Ok(retvl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    #[test]
+    fn firefox_profile() {
+        let root = TempDir::new("firefox");
+        fs::write(
+            root.join("profiles.ini"),
+            "[General]\nStartWithLastProfile=1\n\n\
+             [Profile0]\nName=default\nIsRelative=1\nPath=abcd.default\n\n\
+             [Profile1]\nName=missing\nIsRelative=1\nPath=missing.profile\n",
+        )
+        .unwrap();
+        let profile = root.join("abcd.default");
+        fs::create_dir_all(&profile).unwrap();
+        let conn = Connection::open(profile.join("places.sqlite")).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE moz_places (id INTEGER PRIMARY KEY, url TEXT, title TEXT,
+                 hidden INTEGER, visit_count INTEGER, frecency INTEGER);
+             CREATE TABLE moz_bookmarks (id INTEGER PRIMARY KEY, type INTEGER, fk INTEGER,
+                 parent INTEGER, position INTEGER, title TEXT, guid TEXT);
+             INSERT INTO moz_places VALUES
+                 (1, 'https://www.rust-lang.org/', 'Rust', 0, 5, 100),
+                 (2, 'https://docs.rs/', 'Docs.rs', 0, 9, 500),
+                 (3, 'place:sort=8', NULL, 0, 0, 0),
+                 (4, 'https://example.com/', NULL, 0, 1, 10);
+             INSERT INTO moz_bookmarks VALUES
+                 (1, 2, NULL, 0, 0, '', 'root________'),
+                 (2, 2, NULL, 1, 0, 'toolbar', 'toolbar_____'),
+                 (3, 2, NULL, 1, 1, 'tags', 'tagsfolder_____'),
+                 (4, 2, NULL, 2, 0, 'Rust', 'folder000001'),
+                 (5, 1, 1, 4, 0, 'The Rust Language', 'bookmark0001'),
+                 (6, 1, 3, 2, 1, 'Recent', 'bookmark0002'),
+                 (7, 2, NULL, 3, 0, 'lang', 'tag000000001'),
+                 (8, 1, 1, 7, 0, NULL, 'bookmark0003'),
+                 (9, 2, NULL, 10, 0, 'Loop A', 'folder000002'),
+                 (10, 2, NULL, 9, 0, 'Loop B', 'folder000003'),
+                 (11, 1, 2, 9, 0, 'Looped', 'bookmark0004');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let profiles = firefox_profiles(&root);
+        assert_eq!(vec![profile.clone()], profiles);
+        let (bookmarks, history) = read_firefox(&profile, 10).unwrap();
+
+        assert_eq!(
+            vec![
+                Page {
+                    title: "The Rust Language".to_owned(),
+                    url: "https://www.rust-lang.org/".to_owned(),
+                    folder: Some("toolbar/Rust".to_owned()),
+                },
+                Page {
+                    title: "Looped".to_owned(),
+                    url: "https://docs.rs/".to_owned(),
+                    folder: Some("Loop B/Loop A".to_owned()),
+                }
+            ],
+            bookmarks
+        );
+        let history_urls: Vec<_> = history.iter().map(|page| page.url.as_str()).collect();
+        assert_eq!(
+            vec![
+                "https://docs.rs/",
+                "https://www.rust-lang.org/",
+                "https://example.com/"
+            ],
+            history_urls
+        );
+        let merged = merge(bookmarks, history);
+        assert_eq!(3, merged.len());
+        let entry = merged[2].clone().into_entry(&MimeApps::default());
+        assert_eq!("https://example.com/", entry.name());
+        assert_eq!(vec!["https://example.com/", "history"], entry.search_terms);
+    }
+
+    #[test]
+    fn chromium_profile() {
+        let root = TempDir::new("chromium");
+        let profile = root.join("Default");
+        fs::create_dir_all(&profile).unwrap();
+        fs::create_dir_all(root.join("Crashpad")).unwrap();
+        fs::write(
+            profile.join("Bookmarks"),
+            r#"{
+                "checksum": "abc",
+                "roots": {
+                    "bookmark_bar": {
+                        "type": "folder", "name": "Bookmarks bar",
+                        "children": [
+                            {"type": "url", "name": "Crates", "url": "https://crates.io/"},
+                            {"type": "folder", "name": "Work", "children": [
+                                {"type": "url", "name": "Tracker", "url": "https://bugs.example.com/"}
+                            ]}
+                        ]
+                    },
+                    "other": {"type": "folder", "name": "Other bookmarks", "children": []},
+                    "sync_transaction_version": "1"
+                },
+                "version": 1
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(vec![profile.clone()], chromium_profiles(&root));
+        let bookmarks = read_chromium_bookmarks(&profile).unwrap();
+        let folders: Vec<_> = bookmarks
+            .iter()
+            .map(|page| (page.title.as_str(), page.folder.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("Crates", "Bookmarks bar"),
+                ("Tracker", "Bookmarks bar/Work")
+            ],
+            folders
+        );
+    }
+}
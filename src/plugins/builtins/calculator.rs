@@ -0,0 +1,594 @@
+use crate::config::Config;
+use crate::model::{EntryPlugin, ListEntry, RunFlags};
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Largest exponent we are willing to raise an exact number to before
+/// falling back to floating point.
+const MAX_EXACT_EXPONENT: u32 = 10_000;
+
+/// Largest result, in bits of numerator or denominator, that a power may
+/// produce before falling back to floating point. About 20,000 digits.
+const MAX_EXACT_BITS: u64 = 64 * 1024;
+
+/// Digits shown after the decimal point for non-terminating results.
+const DECIMAL_DIGITS: usize = 12;
+
+pub struct CalculatorPlugin {}
+
+impl CalculatorPlugin {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl EntryPlugin for CalculatorPlugin {
+    fn name(&self) -> String {
+        "Calculator".to_owned()
+    }
+    fn start(&mut self, _config: &Config) {}
+    fn next(&mut self) -> Option<ListEntry> {
+        None
+    }
+    fn query(&mut self, text: &str) -> Vec<ListEntry> {
+        let expr = text.trim();
+        let expr = expr.strip_prefix('=').unwrap_or(expr);
+        // A lone hex or binary literal is still worth converting to decimal.
+        let has_radix = ["0x", "0b", "0o"]
+            .iter()
+            .any(|prefix| expr.to_lowercase().starts_with(prefix));
+        let parsed = match Parser::new(expr).parse() {
+            Ok(parsed) if has_radix || parsed.is_interesting() => parsed,
+            _ => {
+                return Vec::new();
+            }
+        };
+        let value = match parsed.eval() {
+            Ok(value) => value,
+            Err(_) => {
+                return Vec::new();
+            }
+        };
+        let result = value.to_string();
+        let display_name = match &value {
+            Number::Exact(n) if !n.is_integer() && !is_terminating(n) => {
+                format!("= {} ({})", result, n)
+            }
+            _ => format!("= {}", result),
+        };
+        vec![ListEntry {
+            display_name: Some(display_name),
+            search_terms: vec![text.to_owned()],
+            exec_command: vec!["wl-copy".to_owned(), "--".to_owned(), result],
+            exec_flags: RunFlags::new(),
+            children: Vec::new(),
+            source: None,
+            tag: None,
+        }]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    Exact(BigRational),
+    Approx(f64),
+}
+
+impl Number {
+    fn to_f64(&self) -> f64 {
+        match self {
+            Number::Exact(n) => n.to_f64().unwrap_or(f64::NAN),
+            Number::Approx(f) => *f,
+        }
+    }
+
+    fn binary(self, other: Number, op: BinOp) -> Result<Number, String> {
+        match (self, other, op) {
+            (Number::Exact(a), Number::Exact(b), BinOp::Add) => Ok(Number::Exact(a + b)),
+            (Number::Exact(a), Number::Exact(b), BinOp::Sub) => Ok(Number::Exact(a - b)),
+            (Number::Exact(a), Number::Exact(b), BinOp::Mul) => Ok(Number::Exact(a * b)),
+            (Number::Exact(_), Number::Exact(b), BinOp::Div | BinOp::Rem) if b.is_zero() => {
+                Err("Division by zero".to_owned())
+            }
+            (Number::Exact(a), Number::Exact(b), BinOp::Div) => Ok(Number::Exact(a / b)),
+            (Number::Exact(a), Number::Exact(b), BinOp::Rem) => Ok(Number::Exact(a % b)),
+            (Number::Exact(a), Number::Exact(b), BinOp::Pow) => Ok(exact_pow(a, b)),
+            (a, b, op) => {
+                let (a, b) = (a.to_f64(), b.to_f64());
+                let res = match op {
+                    BinOp::Add => a + b,
+                    BinOp::Sub => a - b,
+                    BinOp::Mul => a * b,
+                    BinOp::Div => a / b,
+                    BinOp::Rem => a % b,
+                    BinOp::Pow => a.powf(b),
+                };
+                Ok(Number::Approx(res))
+            }
+        }
+    }
+
+    fn call(self, func: &str) -> Result<Number, String> {
+        if let Number::Exact(n) = &self {
+            let exact = match func {
+                "abs" => Some(n.abs()),
+                "floor" => Some(n.floor()),
+                "ceil" => Some(n.ceil()),
+                "round" => Some(n.round()),
+                "sqrt" => exact_sqrt(n),
+                _ => None,
+            };
+            if let Some(res) = exact {
+                return Ok(Number::Exact(res));
+            }
+        }
+        let f = self.to_f64();
+        let res = match func {
+            "abs" => f.abs(),
+            "floor" => f.floor(),
+            "ceil" => f.ceil(),
+            "round" => f.round(),
+            "sqrt" => f.sqrt(),
+            "cbrt" => f.cbrt(),
+            "exp" => f.exp(),
+            "ln" => f.ln(),
+            "log" | "log10" => f.log10(),
+            "log2" => f.log2(),
+            "sin" => f.sin(),
+            "cos" => f.cos(),
+            "tan" => f.tan(),
+            "asin" => f.asin(),
+            "acos" => f.acos(),
+            "atan" => f.atan(),
+            "sinh" => f.sinh(),
+            "cosh" => f.cosh(),
+            "tanh" => f.tanh(),
+            other => {
+                return Err(format!("Unknown function {}", other));
+            }
+        };
+        Ok(Number::Approx(res))
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Exact(n) if n.is_integer() => write!(f, "{}", n.numer()),
+            Number::Exact(n) => f.write_str(&to_decimal(n)),
+            Number::Approx(v) if *v == 0.0 || (1e-6..1e15).contains(&v.abs()) => {
+                let raw = format!("{:.*}", DECIMAL_DIGITS, v);
+                let trimmed = raw.trim_end_matches('0').trim_end_matches('.');
+                f.write_str(if trimmed == "-0" { "0" } else { trimmed })
+            }
+            Number::Approx(v) => write!(f, "{:e}", v),
+        }
+    }
+}
+
+fn exact_pow(base: BigRational, exp: BigRational) -> Number {
+    let small_exp = if exp.is_integer() {
+        exp.numer().to_i64()
+    } else {
+        None
+    };
+    // The result has about `n` times the bits of the base; checking first
+    // keeps nested powers from computing millions of digits.
+    let fits = |n: i64| {
+        let bits = base.numer().bits().max(base.denom().bits());
+        n.unsigned_abs() <= u64::from(MAX_EXACT_EXPONENT)
+            && bits.saturating_mul(n.unsigned_abs()) <= MAX_EXACT_BITS
+    };
+    match small_exp {
+        Some(n) if fits(n) => {
+            if n < 0 && base.is_zero() {
+                return Number::Approx(f64::INFINITY);
+            }
+            let powered = BigRational::new(
+                base.numer().pow(n.unsigned_abs() as u32),
+                base.denom().pow(n.unsigned_abs() as u32),
+            );
+            if n < 0 {
+                Number::Exact(powered.recip())
+            } else {
+                Number::Exact(powered)
+            }
+        }
+        _ => Number::Approx(
+            Number::Exact(base)
+                .to_f64()
+                .powf(Number::Exact(exp).to_f64()),
+        ),
+    }
+}
+
+fn exact_sqrt(n: &BigRational) -> Option<BigRational> {
+    if n.is_negative() {
+        return None;
+    }
+    let numer = n.numer().sqrt();
+    let denom = n.denom().sqrt();
+    if &numer * &numer == *n.numer() && &denom * &denom == *n.denom() {
+        Some(BigRational::new(numer, denom))
+    } else {
+        None
+    }
+}
+
+/// Whether the decimal expansion of `n` ends, ie its reduced denominator
+/// only has 2 and 5 as prime factors.
+fn is_terminating(n: &BigRational) -> bool {
+    let mut denom = n.denom().clone();
+    for factor in [2u32, 5] {
+        let factor = BigInt::from(factor);
+        while (&denom % &factor).is_zero() {
+            denom /= &factor;
+        }
+    }
+    denom.is_one()
+}
+
+fn to_decimal(n: &BigRational) -> String {
+    let sign = if n.is_negative() { "-" } else { "" };
+    let n = n.abs();
+    let int_part = n.trunc();
+    let mut remainder = n - &int_part;
+    let mut digits = String::new();
+    let ten = BigRational::from_integer(BigInt::from(10));
+    while !remainder.is_zero() && digits.len() < DECIMAL_DIGITS {
+        remainder *= &ten;
+        let digit = remainder.trunc();
+        digits.push_str(&digit.numer().to_string());
+        remainder -= digit;
+    }
+    let trimmed = digits.trim_end_matches('0');
+    if trimmed.is_empty() {
+        format!("{}{}", sign, int_part.numer())
+    } else {
+        format!("{}{}.{}", sign, int_part.numer(), trimmed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Number),
+    Constant(&'static str, f64),
+    Negate(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Call(String, Box<Expr>),
+}
+
+impl Expr {
+    pub fn eval(&self) -> Result<Number, String> {
+        match self {
+            Expr::Literal(n) => Ok(n.clone()),
+            Expr::Constant(_, value) => Ok(Number::Approx(*value)),
+            Expr::Negate(inner) => match inner.eval()? {
+                Number::Exact(n) => Ok(Number::Exact(-n)),
+                Number::Approx(f) => Ok(Number::Approx(-f)),
+            },
+            Expr::Binary(op, lhs, rhs) => lhs.eval()?.binary(rhs.eval()?, *op),
+            Expr::Call(func, arg) => arg.eval()?.call(func),
+        }
+    }
+
+    /// Plain numbers and constants are not worth showing a result for, since
+    /// the result would just repeat what was typed.
+    fn is_interesting(&self) -> bool {
+        !matches!(self, Expr::Literal(_) | Expr::Constant(..))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(Number),
+    Ident(String),
+    Op(BinOp),
+    LParen,
+    RParen,
+}
+
+/// Recursive descent parser over the usual precedence levels:
+/// `+ -` < `* / %` < unary minus < `^`, with `^` being right associative.
+pub struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    peeked: Option<Token>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(raw: &'a str) -> Self {
+        Self {
+            chars: raw.chars().peekable(),
+            peeked: None,
+        }
+    }
+
+    pub fn parse(mut self) -> Result<Expr, String> {
+        let expr = self.expr()?;
+        match self.next_token()? {
+            None => {}
+            Some(tok) => {
+                return Err(format!("Unexpected token {:?}", tok));
+            }
+        }
+        Ok(expr)
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.term()?;
+        while let Some(Token::Op(op @ BinOp::Add)) | Some(Token::Op(op @ BinOp::Sub)) =
+            self.peek_token()?
+        {
+            self.next_token()?;
+            let rhs = self.term()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Op(op @ BinOp::Mul))
+        | Some(Token::Op(op @ BinOp::Div))
+        | Some(Token::Op(op @ BinOp::Rem)) = self.peek_token()?
+        {
+            self.next_token()?;
+            let rhs = self.unary()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.peek_token()? {
+            Some(Token::Op(BinOp::Sub)) => {
+                self.next_token()?;
+                Ok(Expr::Negate(Box::new(self.unary()?)))
+            }
+            Some(Token::Op(BinOp::Add)) => {
+                self.next_token()?;
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.atom()?;
+        if let Some(Token::Op(BinOp::Pow)) = self.peek_token()? {
+            self.next_token()?;
+            let exponent = self.unary()?;
+            return Ok(Expr::Binary(BinOp::Pow, Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.next_token()? {
+            Some(Token::Number(n)) => Ok(Expr::Literal(n)),
+            Some(Token::LParen) => {
+                let inner = self.expr()?;
+                self.expect_rparen()?;
+                Ok(inner)
+            }
+            Some(Token::Ident(name)) => match name.as_str() {
+                "pi" => Ok(Expr::Constant("pi", std::f64::consts::PI)),
+                "e" => Ok(Expr::Constant("e", std::f64::consts::E)),
+                "tau" => Ok(Expr::Constant("tau", std::f64::consts::TAU)),
+                _ => {
+                    if self.next_token()? != Some(Token::LParen) {
+                        return Err(format!("Expected ( after {}", name));
+                    }
+                    let arg = self.expr()?;
+                    self.expect_rparen()?;
+                    Ok(Expr::Call(name, Box::new(arg)))
+                }
+            },
+            Some(other) => Err(format!("Unexpected token {:?}", other)),
+            None => Err("Unexpected end of expression".to_owned()),
+        }
+    }
+
+    fn expect_rparen(&mut self) -> Result<(), String> {
+        match self.next_token()? {
+            Some(Token::RParen) => Ok(()),
+            other => Err(format!("Expected ), found {:?}", other)),
+        }
+    }
+
+    fn peek_token(&mut self) -> Result<Option<Token>, String> {
+        if self.peeked.is_none() {
+            self.peeked = self.lex()?;
+        }
+        Ok(self.peeked.clone())
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, String> {
+        match self.peeked.take() {
+            Some(tok) => Ok(Some(tok)),
+            None => self.lex(),
+        }
+    }
+
+    fn lex(&mut self) -> Result<Option<Token>, String> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let c = match self.chars.next() {
+            Some(c) => c,
+            None => {
+                return Ok(None);
+            }
+        };
+        let tok = match c {
+            '+' => Token::Op(BinOp::Add),
+            '-' | '−' => Token::Op(BinOp::Sub),
+            '*' if self.chars.next_if_eq(&'*').is_some() => Token::Op(BinOp::Pow),
+            '*' | '×' => Token::Op(BinOp::Mul),
+            '/' | '÷' => Token::Op(BinOp::Div),
+            '%' => Token::Op(BinOp::Rem),
+            '^' => Token::Op(BinOp::Pow),
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '0'..='9' | '.' => Token::Number(self.lex_number(c)?),
+            c if c.is_alphabetic() => {
+                let mut name = c.to_string();
+                while let Some(c) = self.chars.next_if(|c| c.is_alphanumeric()) {
+                    name.push(c);
+                }
+                Token::Ident(name.to_lowercase())
+            }
+            other => {
+                return Err(format!("Unexpected character {:?}", other));
+            }
+        };
+        Ok(Some(tok))
+    }
+
+    fn lex_number(&mut self, first: char) -> Result<Number, String> {
+        if first == '0' {
+            let radix = match self.chars.peek() {
+                Some('x') | Some('X') => Some(16),
+                Some('b') | Some('B') => Some(2),
+                Some('o') | Some('O') => Some(8),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                self.chars.next();
+                let mut digits = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_digit(radix) || *c == '_') {
+                    if c != '_' {
+                        digits.push(c);
+                    }
+                }
+                let n = BigInt::parse_bytes(digits.as_bytes(), radix)
+                    .ok_or_else(|| format!("Invalid base {} literal", radix))?;
+                return Ok(Number::Exact(BigRational::from_integer(n)));
+            }
+        }
+        let mut int_digits = String::new();
+        let mut frac_digits = String::new();
+        let mut in_fraction = first == '.';
+        if !in_fraction {
+            int_digits.push(first);
+        }
+        while let Some(c) = self
+            .chars
+            .next_if(|c| c.is_ascii_digit() || *c == '_' || (*c == '.' && !in_fraction))
+        {
+            match c {
+                '.' => {
+                    in_fraction = true;
+                }
+                '_' => {}
+                c if in_fraction => frac_digits.push(c),
+                c => int_digits.push(c),
+            }
+        }
+        if int_digits.is_empty() && frac_digits.is_empty() {
+            return Err("Invalid number".to_owned());
+        }
+        let all_digits = format!("{}{}", int_digits, frac_digits);
+        let numer = BigInt::parse_bytes(all_digits.as_bytes(), 10)
+            .ok_or_else(|| "Invalid number".to_owned())?;
+        let denom = BigInt::from(10).pow(frac_digits.len() as u32);
+        Ok(Number::Exact(BigRational::new(numer, denom)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(raw: &str) -> String {
+        Parser::new(raw)
+            .parse()
+            .unwrap()
+            .eval()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!("7", eval("1 + 2 * 3"));
+        assert_eq!("9", eval("(1 + 2) * 3"));
+        assert_eq!("-4", eval("-2^2"));
+        assert_eq!("512", eval("2^3^2"));
+        assert_eq!("0.25", eval("2^-2"));
+        assert_eq!("1", eval("10 % 3"));
+        assert_eq!("8", eval("2**3"));
+        assert_eq!("2.5", eval("10 / 4"));
+    }
+
+    #[test]
+    fn exact() {
+        assert_eq!("0.3", eval("0.1 + 0.2"));
+        assert_eq!("0.333333333333", eval("1/3"));
+        assert_eq!("1", eval("1/3 * 3"));
+        assert_eq!("4", eval("sqrt(16)"));
+        assert_eq!("1.5", eval("sqrt(9/4)"));
+        assert_eq!("1267650600228229401496703205376", eval("2^100"));
+        assert_eq!(3011, eval("2^10000").len());
+        assert_eq!("inf", eval("(9^9999)^9999"));
+        assert_eq!(
+            "340282366920938463463374607431768211456",
+            eval("0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF + 1")
+        );
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!("255", eval("0xff"));
+        assert_eq!("5", eval("0b101"));
+        assert_eq!("8", eval("0o10"));
+        assert_eq!("1000000", eval("1_000_000"));
+        assert_eq!("0.5", eval(".5"));
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!("0", eval("sin(0)"));
+        assert_eq!("2", eval("log(100)"));
+        assert_eq!("1", eval("ln(e)"));
+        assert_eq!("1.414213562373", eval("sqrt(2)"));
+        assert_eq!("3", eval("abs(-3)"));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Parser::new("1 +").parse().is_err());
+        assert!(Parser::new("(1 + 2").parse().is_err());
+        assert!(Parser::new("firefox").parse().is_err());
+        assert!(Parser::new("foo(1)").parse().unwrap().eval().is_err());
+        assert!(Parser::new("1 / 0").parse().unwrap().eval().is_err());
+    }
+
+    #[test]
+    fn query_entries() {
+        let mut plugin = CalculatorPlugin::new();
+        assert!(plugin.query("firefox").is_empty());
+        assert!(plugin.query("42").is_empty());
+        assert!(plugin.query("pi").is_empty());
+        let res = plugin.query("2 + 2");
+        assert_eq!(1, res.len());
+        assert_eq!("= 4", res[0].name());
+        assert_eq!(vec!["wl-copy", "--", "4"], res[0].exec_command);
+        assert_eq!("= 255", plugin.query("0xff")[0].name());
+        assert_eq!("= 0.333333333333 (1/3)", plugin.query("1/3")[0].name());
+    }
+}
@@ -0,0 +1,245 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::model::{EntryPlugin, ListEntry, RunFlags};
+
+/// What selecting a character does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmojiAction {
+    /// Copies the character with `wl-copy`.
+    Copy,
+    /// Types the character into the focused window with `wtype`.
+    Type,
+}
+
+/// The `[emoji]` table of the config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmojiConfig {
+    pub action: EmojiAction,
+    /// The maximum number of characters to list for a query.
+    pub limit: usize,
+}
+
+impl Default for EmojiConfig {
+    fn default() -> Self {
+        Self {
+            action: EmojiAction::Copy,
+            limit: 30,
+        }
+    }
+}
+
+pub struct EmojiPlugin {
+    config: EmojiConfig,
+    /// Every named Unicode character, built the first time it is needed.
+    names: Option<Vec<(char, String)>>,
+}
+
+impl EmojiPlugin {
+    pub fn new() -> Self {
+        Self {
+            config: EmojiConfig::default(),
+            names: None,
+        }
+    }
+}
+
+impl EntryPlugin for EmojiPlugin {
+    fn name(&self) -> String {
+        "Emoji".to_owned()
+    }
+    fn start(&mut self, config: &Config) {
+        self.config = config.emoji.clone();
+    }
+    fn next(&mut self) -> Option<ListEntry> {
+        None
+    }
+    fn query(&mut self, text: &str) -> Vec<ListEntry> {
+        let trimmed = text.trim();
+        let found = if let Some(term) = trimmed.strip_prefix(':') {
+            search_emoji(term)
+        } else if let Some(term) = strip_keyword(trimmed, "emoji") {
+            search_emoji(term)
+        } else if let Some(term) = strip_keyword(trimmed, "unicode") {
+            let names = self.names.get_or_insert_with(unicode_names);
+            search_unicode(names, term)
+        } else {
+            return Vec::new();
+        };
+        found
+            .into_iter()
+            .take(self.config.limit)
+            .map(|found| found.into_entry(text, self.config.action))
+            .collect()
+    }
+}
+
+fn strip_keyword<'a>(text: &'a str, keyword: &str) -> Option<&'a str> {
+    let (first, rest) = text.split_once(' ')?;
+    if first.eq_ignore_ascii_case(keyword) {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Found {
+    value: String,
+    description: String,
+    variants: Vec<Found>,
+}
+
+impl Found {
+    fn into_entry(self, query: &str, action: EmojiAction) -> ListEntry {
+        let exec_command = match action {
+            EmojiAction::Copy => vec!["wl-copy".to_owned(), "--".to_owned(), self.value],
+            EmojiAction::Type => vec!["wtype".to_owned(), "--".to_owned(), self.value],
+        };
+        ListEntry {
+            display_name: Some(self.description),
+            search_terms: vec![query.to_owned()],
+            exec_command,
+            exec_flags: RunFlags::new(),
+            children: self
+                .variants
+                .into_iter()
+                .map(|variant| variant.into_entry(query, action))
+                .collect(),
+            source: None,
+            tag: None,
+        }
+    }
+}
+
+/// Matches emoji by shortcode and CLDR name, with skin tone variants as
+/// children of their default-toned emoji.
+fn search_emoji(term: &str) -> Vec<Found> {
+    let term = term.trim_end_matches(':').to_lowercase();
+    if term.is_empty() {
+        return Vec::new();
+    }
+    let describe = |emoji: &emojis::Emoji| match emoji.shortcode() {
+        Some(code) => format!("{}  {} :{}:", emoji.as_str(), emoji.name(), code),
+        None => format!("{}  {}", emoji.as_str(), emoji.name()),
+    };
+    let mut ranked: Vec<(u8, &emojis::Emoji)> = emojis::iter()
+        .filter_map(|emoji| {
+            let mut codes = emoji.shortcodes();
+            let rank = if codes.clone().any(|code| code == term) {
+                0
+            } else if codes.clone().any(|code| code.starts_with(&term)) {
+                1
+            } else if codes.any(|code| code.contains(&term)) || emoji.name().contains(&term) {
+                2
+            } else {
+                return None;
+            };
+            Some((rank, emoji))
+        })
+        .collect();
+    ranked.sort_by_key(|(rank, _)| *rank);
+    ranked
+        .into_iter()
+        .map(|(_, emoji)| Found {
+            value: emoji.as_str().to_owned(),
+            description: describe(emoji),
+            variants: emoji
+                .skin_tones()
+                .into_iter()
+                .flatten()
+                .skip(1)
+                .map(|variant| Found {
+                    value: variant.as_str().to_owned(),
+                    description: describe(variant),
+                    variants: Vec::new(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+fn unicode_names() -> Vec<(char, String)> {
+    (0..=0x10FFFF)
+        .filter_map(std::char::from_u32)
+        .filter_map(|c| Some((c, unicode_names2::name(c)?.to_string())))
+        .collect()
+}
+
+/// Matches characters whose name contains every word of the term, or the
+/// single character given as a `U+XXXX` code point.
+fn search_unicode(names: &[(char, String)], term: &str) -> Vec<Found> {
+    let describe = |c: char, name: &str| Found {
+        value: c.to_string(),
+        description: format!("{}  {} (U+{:04X})", c, name, c as u32),
+        variants: Vec::new(),
+    };
+    let code_point = term
+        .strip_prefix("U+")
+        .or_else(|| term.strip_prefix("u+"))
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .and_then(std::char::from_u32);
+    if let Some(c) = code_point {
+        let name = unicode_names2::name(c).map(|name| name.to_string());
+        return vec![describe(c, name.as_deref().unwrap_or("unnamed"))];
+    }
+    let words: Vec<String> = term.split_whitespace().map(|w| w.to_uppercase()).collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
+    let mut matched: Vec<&(char, String)> = names
+        .iter()
+        .filter(|(_, name)| words.iter().all(|word| name.contains(word.as_str())))
+        .collect();
+    // Shorter names are usually the more basic characters.
+    matched.sort_by_key(|(_, name)| name.len());
+    matched
+        .into_iter()
+        .map(|(c, name)| describe(*c, name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emoji_search() {
+        let found = search_emoji("thumbsup");
+        assert_eq!("👍", found[0].value);
+        let tones: Vec<_> = found[0].variants.iter().map(|v| v.value.as_str()).collect();
+        assert_eq!(vec!["👍🏻", "👍🏼", "👍🏽", "👍🏾", "👍🏿"], tones);
+
+        let found = search_emoji("sunglasses:");
+        assert_eq!("😎", found[0].value);
+        assert!(found[0].variants.is_empty());
+        assert!(search_emoji("").is_empty());
+    }
+
+    #[test]
+    fn unicode_search() {
+        let names = vec![
+            ('→', "RIGHTWARDS ARROW".to_owned()),
+            ('⇒', "RIGHTWARDS DOUBLE ARROW".to_owned()),
+            ('←', "LEFTWARDS ARROW".to_owned()),
+        ];
+        let found: Vec<_> = search_unicode(&names, "right arrow")
+            .into_iter()
+            .map(|found| found.description)
+            .collect();
+        assert_eq!(
+            vec![
+                "→  RIGHTWARDS ARROW (U+2192)",
+                "⇒  RIGHTWARDS DOUBLE ARROW (U+21D2)"
+            ],
+            found
+        );
+        let found = search_unicode(&names, "U+00E9");
+        assert_eq!(
+            "é  LATIN SMALL LETTER E WITH ACUTE (U+00E9)",
+            found[0].description
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::model::{EntryPlugin, ListEntry, RunFlags};
+use crate::plugins::builtins::mimeapps::MimeApps;
+use crate::utils::{contract_tilde, expand_tilde};
+
+/// The `[files]` table of the config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilesConfig {
+    /// Directories to index; a leading `~` expands to the home directory.
+    pub roots: Vec<String>,
+    /// How many directories deep to descend below each root.
+    pub max_depth: usize,
+    /// Globs for paths to skip, matched against both the file name and the
+    /// full path.
+    pub ignore: Vec<String>,
+    /// Whether to respect `.gitignore` and `.ignore` files.
+    pub gitignore: bool,
+    /// Whether to index hidden files and directories.
+    pub hidden: bool,
+    /// The maximum number of entries to show when browsing a typed path.
+    pub browse_limit: usize,
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        Self {
+            roots: vec!["~".to_owned()],
+            max_depth: 3,
+            ignore: Vec::new(),
+            gitignore: true,
+            hidden: false,
+            browse_limit: 50,
+        }
+    }
+}
+
+pub struct FilesPlugin {
+    config: FilesConfig,
+    mime: Rc<MimeApps>,
+    inner: Box<dyn Iterator<Item = ListEntry>>,
+}
+
+impl FilesPlugin {
+    pub fn new() -> Self {
+        Self {
+            config: FilesConfig::default(),
+            mime: Rc::default(),
+            inner: Box::new(None.into_iter()),
+        }
+    }
+}
+
+impl EntryPlugin for FilesPlugin {
+    fn name(&self) -> String {
+        "Files".to_owned()
+    }
+    fn start(&mut self, config: &Config) {
+        self.config = config.files.clone();
+        self.mime = Rc::new(MimeApps::load());
+        self.inner = Box::new(walk(&self.config, Rc::clone(&self.mime)));
+    }
+    fn next(&mut self) -> Option<ListEntry> {
+        self.inner.next()
+    }
+    fn query(&mut self, text: &str) -> Vec<ListEntry> {
+        browse(
+            text,
+            self.config.hidden,
+            self.config.browse_limit,
+            &self.mime,
+        )
+    }
+}
+
+fn ignore_globs(patterns: &[String]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => {
+                eprintln!("ERROR: Invalid files ignore glob {:?}: {}", pattern, e);
+            }
+        }
+    }
+    builder.build().unwrap_or_else(|e| {
+        eprintln!("ERROR: Could not build files ignore globs: {}", e);
+        GlobSet::empty()
+    })
+}
+
+fn walk(config: &FilesConfig, mime: Rc<MimeApps>) -> impl Iterator<Item = ListEntry> {
+    let mut roots = config.roots.iter().map(|root| expand_tilde(root));
+    let mut builder = match roots.next() {
+        Some(first) => WalkBuilder::new(first),
+        None => {
+            return None.into_iter().flatten();
+        }
+    };
+    for root in roots {
+        builder.add(root);
+    }
+    let ignored = ignore_globs(&config.ignore);
+    builder
+        .max_depth(Some(config.max_depth))
+        .hidden(!config.hidden)
+        .git_ignore(config.gitignore)
+        .git_global(config.gitignore)
+        .git_exclude(config.gitignore)
+        .ignore(config.gitignore)
+        .filter_entry(move |ent| {
+            let name_ignored = ignored.is_match(ent.file_name());
+            !name_ignored && !ignored.is_match(ent.path())
+        });
+    let entries = builder
+        .build()
+        .filter_map(|res| match res {
+            Ok(ent) => Some(ent),
+            Err(e) => {
+                eprintln!("ERROR From files plugin: {}", e);
+                None
+            }
+        })
+        .filter(|ent| ent.depth() > 0)
+        .map(move |ent| {
+            let is_dir = ent.file_type().is_some_and(|ft| ft.is_dir());
+            let display = contract_tilde(ent.path());
+            make_entry(ent.into_path(), display, is_dir, &mime)
+        });
+    Some(entries).into_iter().flatten()
+}
+
+/// Lists the contents of a typed path like `~/Doc` or `/etc/`, completing the
+/// last component as a prefix.
+fn browse(text: &str, hidden: bool, limit: usize, mime: &MimeApps) -> Vec<ListEntry> {
+    if !(text.starts_with('/') || text.starts_with('~')) {
+        return Vec::new();
+    }
+    let (typed_dir, prefix) = match text.rfind('/') {
+        Some(idx) => text.split_at(idx + 1),
+        None => (text, ""),
+    };
+    let typed_dir = if typed_dir.ends_with('/') {
+        typed_dir.to_owned()
+    } else {
+        format!("{}/", typed_dir)
+    };
+    let dir = expand_tilde(&typed_dir);
+    let read = match fs::read_dir(&dir) {
+        Ok(read) => read,
+        Err(_) => {
+            return Vec::new();
+        }
+    };
+    let prefix = prefix.to_lowercase();
+    let show_hidden = hidden || prefix.starts_with('.');
+    let mut found: Vec<(bool, String, PathBuf)> = read
+        .filter_map(|res| res.ok())
+        .filter_map(|ent| {
+            let name = ent.file_name().to_string_lossy().into_owned();
+            if !name.to_lowercase().starts_with(&prefix) {
+                return None;
+            }
+            if name.starts_with('.') && !show_hidden {
+                return None;
+            }
+            let is_dir = ent.path().is_dir();
+            Some((is_dir, name, ent.path()))
+        })
+        .collect();
+    found.sort_by(|(a_dir, a_name, _), (b_dir, b_name, _)| {
+        b_dir.cmp(a_dir).then_with(|| a_name.cmp(b_name))
+    });
+
+    let mut retvl = Vec::new();
+    if prefix.is_empty() {
+        retvl.push(make_entry(dir, typed_dir.clone(), true, mime));
+    }
+    retvl.extend(found.into_iter().take(limit).map(|(is_dir, name, path)| {
+        make_entry(path, format!("{}{}", typed_dir, name), is_dir, mime)
+    }));
+    retvl
+}
+
+fn make_entry(path: PathBuf, display: String, is_dir: bool, mime: &MimeApps) -> ListEntry {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    // Walking a directory tree lists far too many files to read each one
+    // for its type.
+    let (exec_command, exec_flags) = mime.open_file_by_name(&path, is_dir);
+    let (display, children) = if is_dir {
+        let display = if display.ends_with('/') {
+            display
+        } else {
+            format!("{}/", display)
+        };
+        (display, directory_actions(&path, mime))
+    } else {
+        (display, Vec::new())
+    };
+    ListEntry {
+        display_name: Some(display),
+        search_terms: vec![file_name],
+        exec_command,
+        exec_flags,
+        children,
+        source: Some(path),
+        tag: None,
+    }
+}
+
+fn directory_actions(path: &Path, mime: &MimeApps) -> Vec<ListEntry> {
+    let path_str = path.display().to_string();
+    let (exec_command, exec_flags) = mime.open_file_by_name(path, true);
+    let file_manager = ListEntry {
+        display_name: Some("Open in file manager".to_owned()),
+        exec_command,
+        exec_flags,
+        ..Default::default()
+    };
+    let terminal = ListEntry {
+        display_name: Some("Open terminal here".to_owned()),
+        exec_command: vec![
+            "sh".to_owned(),
+            "-c".to_owned(),
+            "cd \"$1\" && exec \"${SHELL:-sh}\"".to_owned(),
+            "sh".to_owned(),
+            path_str,
+        ],
+        exec_flags: RunFlags::new().with_term(true),
+        ..Default::default()
+    };
+    vec![file_manager, terminal]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    #[test]
+    fn browse_paths() {
+        let root = TempDir::new("files");
+        fs::create_dir_all(root.join("Documents")).unwrap();
+        fs::create_dir_all(root.join("Downloads")).unwrap();
+        fs::write(root.join("notes.txt"), "").unwrap();
+        fs::write(root.join(".hidden"), "").unwrap();
+        let typed = root.display().to_string();
+
+        let names = |text: &str| -> Vec<String> {
+            browse(text, false, 50, &MimeApps::default())
+                .into_iter()
+                .map(|ent| ent.name().to_owned())
+                .collect()
+        };
+        assert_eq!(
+            vec![
+                format!("{}/", typed),
+                format!("{}/Documents/", typed),
+                format!("{}/Downloads/", typed),
+                format!("{}/notes.txt", typed),
+            ],
+            names(&format!("{}/", typed))
+        );
+        assert_eq!(
+            vec![format!("{}/Documents/", typed)],
+            names(&format!("{}/doc", typed))
+        );
+        assert_eq!(
+            vec![format!("{}/.hidden", typed)],
+            names(&format!("{}/.h", typed))
+        );
+        assert!(names("firefox").is_empty());
+    }
+}
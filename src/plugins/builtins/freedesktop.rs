@@ -1,8 +1,7 @@
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
-use std::iter;
-use std::mem;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 
 use crate::config::Config;
 use crate::model::{EntryPlugin, ListEntry, RunFlags};
@@ -12,6 +11,7 @@ mod parsing;
 mod searching;
 
 use parsing::SectionReader;
+pub use searching::{desktop_file_ids, xdg_config_dirs, xdg_config_home, xdg_data_dirs, xdg_data_homes};
 
 pub struct FreedesktopPlugin {
     inner: Box<dyn Iterator<Item = ListEntry>>,
@@ -35,14 +35,17 @@ impl EntryPlugin for FreedesktopPlugin {
             .filter_map(filter_log(|e| {
                 eprintln!("ERROR from xdg: {:?}", e);
             }))
-            .flat_map(move |sections| {
+            .flat_map(move |(path, sections)| {
                 let mut parent = None;
                 let mut children = Vec::new();
                 let mut errors = Vec::new();
                 for section in sections {
                     let is_parent = section.header == "Desktop Entry";
 
-                    let res = section_to_entry(section, language.as_deref());
+                    let res = section_to_entry(section, language.as_deref()).map(|mut ent| {
+                        ent.source = Some(path.clone());
+                        ent
+                    });
                     match res {
                         Ok(ent) if is_parent && parent.is_none() => {
                             parent = Some(ent);
@@ -114,41 +117,33 @@ fn section_to_entry(section: Section, language: Option<&str>) -> Result<ListEntr
         exec_flags,
         search_terms,
         children,
+        source: None,
+        tag: None,
     };
     Ok(res)
 }
 
-fn get_sections() -> impl Iterator<Item = io::Result<Vec<Section>>> {
+fn get_sections() -> impl Iterator<Item = io::Result<(PathBuf, Vec<Section>)>> {
     searching::xdg_desktop_files().map(|path_res| {
-        let file_res = path_res.and_then(File::open).map(BufReader::new);
-        let file = match file_res {
-            Ok(file) => file,
-            Err(e) => {
-                return Err(e);
-            }
-        };
-        let mut reader = SectionReader::new();
-        let mut lines = file.lines().peekable();
-
-        iter::from_fn(move || loop {
-            let raw_line = lines.next()?;
-            let raw_line = match raw_line {
-                Ok(l) => l,
-                Err(e) => {
-                    return Some(Err(e));
-                }
-            };
-            if let Some(next) = reader.push(raw_line.as_ref()) {
-                return Some(Ok(next));
-            }
-            if lines.peek().is_none() {
-                return mem::take(&mut reader).finish().map(Ok);
-            }
-        })
-        .collect::<io::Result<Vec<_>>>()
+        let path = path_res?;
+        read_sections(&path).map(|sections| (path, sections))
     })
 }
 
+/// Reads every section out of an INI-style file such as a desktop entry or
+/// `mimeapps.list`.
+pub fn read_sections(path: &Path) -> io::Result<Vec<Section>> {
+    Ok(parse_sections(&fs::read_to_string(path)?))
+}
+
+/// Like `read_sections`, for text that is already in memory.
+pub fn parse_sections(raw: &str) -> Vec<Section> {
+    let mut reader = SectionReader::new();
+    let mut retvl: Vec<Section> = raw.lines().filter_map(|line| reader.push(line)).collect();
+    retvl.extend(reader.finish());
+    retvl
+}
+
 #[derive(Default, Debug)]
 pub struct Section {
     pub header: String,
@@ -191,7 +186,7 @@ impl Section {
             .map(|s| s.as_ref())
     }
 
-    fn get_field<'a>(&self, name: &'a str) -> Option<&str> {
+    pub fn get_field<'a>(&self, name: &'a str) -> Option<&str> {
         let ent = self.fields.get(name)?;
         let default = ent.default.as_ref()?;
         Some(default.as_ref())
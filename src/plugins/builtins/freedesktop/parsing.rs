@@ -86,18 +86,15 @@ impl SectionReader {
                     None => entmap.default.replace(value.to_owned()),
                 };
                 if let Some(old) = old {
-                    todo!(
-                        "Handle dup entries: {:?}/{:?} => {:?}, {:?}",
-                        key,
-                        attribute,
-                        old,
-                        value
+                    eprintln!(
+                        "WARNING: Duplicate key {:?}/{:?}; replacing {:?} with {:?}",
+                        key, attribute, old, value
                     );
                 }
             }
             Ok(LineKind::Comment(..)) | Ok(LineKind::Whitespace) => {}
             Err(e) => {
-                todo!("Found parser error for line : {:?}", e);
+                eprintln!("WARNING: Skipping unparseable line : {:?}", e);
             }
         }
         None
@@ -1,5 +1,6 @@
 use crate::utils::EitherOps;
 
+use std::collections::HashSet;
 use std::fs;
 use std::io;
 use std::iter;
@@ -18,7 +19,7 @@ pub fn xdg_desktop_files() -> impl Iterator<Item = Result<PathBuf, io::Error>> {
     application_dirs.flat_map(desktop_files_in_dir)
 }
 
-fn xdg_data_homes() -> impl Iterator<Item = PathBuf> {
+pub fn xdg_data_homes() -> impl Iterator<Item = PathBuf> {
     let raw_env_val = env::var_os("XDG_DATA_HOME");
 
     match raw_env_val {
@@ -38,7 +39,7 @@ fn xdg_data_homes() -> impl Iterator<Item = PathBuf> {
     }
 }
 
-fn xdg_data_dirs() -> impl Iterator<Item = PathBuf> {
+pub fn xdg_data_dirs() -> impl Iterator<Item = PathBuf> {
     let raw_env_val = env::var_os("XDG_DATA_DIRS");
     match raw_env_val {
         Some(val) => env::split_paths(&val)
@@ -53,6 +54,65 @@ fn xdg_data_dirs() -> impl Iterator<Item = PathBuf> {
     }
 }
 
+pub fn xdg_config_home() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+}
+
+pub fn xdg_config_dirs() -> impl Iterator<Item = PathBuf> {
+    let raw_env_val = env::var_os("XDG_CONFIG_DIRS");
+    match raw_env_val {
+        Some(val) => env::split_paths(&val)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .left(),
+        None => iter::once(PathBuf::from("/etc/xdg")).right(),
+    }
+}
+
+/// Every installed desktop file along with its desktop file ID, which is its
+/// path below the `applications` directory with `/` replaced by `-`. Earlier
+/// data directories take precedence, so only the first file for each ID is
+/// returned.
+pub fn desktop_file_ids() -> Vec<(String, PathBuf)> {
+    let mut seen = HashSet::new();
+    let mut retvl = Vec::new();
+    let application_dirs = xdg_data_homes()
+        .chain(xdg_data_dirs())
+        .map(|data_dir| data_dir.join("applications"));
+    for app_dir in application_dirs {
+        let mut pending = vec![app_dir.clone()];
+        while let Some(dir) = pending.pop() {
+            let ent_iter = match fs::read_dir(&dir) {
+                Ok(it) => it,
+                Err(_) => {
+                    continue;
+                }
+            };
+            for path in ent_iter.filter_map(|ent| Some(ent.ok()?.path())) {
+                if path.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                if path.extension() != Some(OsStr::new("desktop")) {
+                    continue;
+                }
+                let id = match path.strip_prefix(&app_dir) {
+                    Ok(rel) => rel.to_string_lossy().replace('/', "-"),
+                    Err(_) => {
+                        continue;
+                    }
+                };
+                if seen.insert(id.clone()) {
+                    retvl.push((id, path));
+                }
+            }
+        }
+    }
+    retvl
+}
+
 fn desktop_files_in_dir<D: AsRef<Path>>(
     dir: D,
 ) -> impl Iterator<Item = Result<PathBuf, io::Error>> {
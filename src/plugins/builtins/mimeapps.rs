@@ -0,0 +1,493 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::model::RunFlags;
+use crate::plugins::builtins::freedesktop::{
+    desktop_file_ids, read_sections, xdg_config_dirs, xdg_config_home, xdg_data_dirs,
+    xdg_data_homes, Section,
+};
+
+use percent_encoding::percent_decode_str;
+
+mod mimetypes;
+
+use mimetypes::MimeDatabase;
+
+/// Resolves the default application for files and URLs the same way
+/// `xdg-mime` does, using `mimeapps.list` and the `MimeType=` key of each
+/// installed desktop file.
+#[derive(Debug, Default)]
+pub struct MimeApps {
+    types: MimeDatabase,
+    lists: Vec<AssociationList>,
+    apps: HashMap<String, DesktopApp>,
+    app_order: Vec<String>,
+}
+
+/// The contents of a single `mimeapps.list`.
+#[derive(Debug, Default)]
+struct AssociationList {
+    defaults: HashMap<String, Vec<String>>,
+    added: HashMap<String, Vec<String>>,
+    removed: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DesktopApp {
+    pub path: PathBuf,
+    pub name: String,
+    pub exec: String,
+    pub icon: Option<String>,
+    pub terminal: bool,
+    pub mime_types: Vec<String>,
+}
+
+impl MimeApps {
+    pub fn load() -> Self {
+        let types = MimeDatabase::load();
+        let lists = mimeapps_paths()
+            .iter()
+            .filter(|path| path.is_file())
+            .filter_map(|path| match read_sections(path) {
+                Ok(sections) => Some(AssociationList::from_sections(&sections)),
+                Err(e) => {
+                    eprintln!("ERROR: Could not read {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .collect();
+        let apps = desktop_file_ids()
+            .into_iter()
+            .filter_map(|(id, path)| {
+                let sections = read_sections(&path).ok()?;
+                let section = sections.iter().find(|s| s.header == "Desktop Entry")?;
+                Some((id, DesktopApp::from_section(path.clone(), section)?))
+            })
+            .collect();
+        Self::from_parts(types, lists, apps)
+    }
+
+    fn from_parts(
+        types: MimeDatabase,
+        lists: Vec<AssociationList>,
+        apps: Vec<(String, DesktopApp)>,
+    ) -> Self {
+        let lists: Vec<AssociationList> = lists
+            .into_iter()
+            .map(|list| list.unaliased(&types))
+            .collect();
+        let app_order = apps.iter().map(|(id, _)| id.clone()).collect();
+        let apps = apps
+            .into_iter()
+            .map(|(id, mut app)| {
+                app.mime_types = app
+                    .mime_types
+                    .iter()
+                    .map(|mime| types.unalias(mime).to_owned())
+                    .collect();
+                (id, app)
+            })
+            .collect();
+        Self {
+            types,
+            lists,
+            apps,
+            app_order,
+        }
+    }
+
+    pub fn mime_type(&self, path: &Path) -> String {
+        self.types.guess(path)
+    }
+
+    /// The preferred application for a MIME type, falling back to the
+    /// handlers of the types it is a subclass of.
+    pub fn default_app(&self, mime: &str) -> Option<&DesktopApp> {
+        let mime = self.types.unalias(mime);
+        let parents = self.types.parents(mime);
+        std::iter::once(mime)
+            .chain(parents.iter().map(|s| s.as_str()))
+            .find_map(|mime| self.default_app_exact(mime))
+    }
+
+    fn default_app_exact(&self, mime: &str) -> Option<&DesktopApp> {
+        let installed = |id: &String| self.apps.get(id);
+        let defaults = self
+            .lists
+            .iter()
+            .flat_map(|list| list.defaults.get(mime).into_iter().flatten());
+        if let Some(app) = defaults.filter_map(installed).next() {
+            return Some(app);
+        }
+
+        // Removals only hide associations from lower-priority sources.
+        let mut removed = HashSet::new();
+        for list in self.lists.iter() {
+            let added = list.added.get(mime).into_iter().flatten();
+            if let Some(app) = added
+                .filter(|id| !removed.contains(id))
+                .filter_map(installed)
+                .next()
+            {
+                return Some(app);
+            }
+            removed.extend(list.removed.get(mime).into_iter().flatten());
+        }
+        self.app_order
+            .iter()
+            .filter(|id| !removed.contains(id))
+            .filter_map(installed)
+            .find(|app| app.mime_types.iter().any(|m| m == mime))
+    }
+
+    /// The command that opens a local file in its default application,
+    /// falling back to `xdg-open` when no handler is known.
+    pub fn open_file(&self, path: &Path) -> (Vec<String>, RunFlags) {
+        let target = path.display().to_string();
+        self.open_with(&self.mime_type(path), target)
+    }
+
+    /// Like `open_file`, but without reading the file: a file whose type
+    /// cannot be told from its name is left for `xdg-open` to work out when
+    /// it is opened. For listing many files at once.
+    pub fn open_file_by_name(&self, path: &Path, is_dir: bool) -> (Vec<String>, RunFlags) {
+        let target = path.display().to_string();
+        match self.types.guess_from_name(path, is_dir) {
+            Some(mime) => self.open_with(&mime, target),
+            None => (vec!["xdg-open".to_owned(), target], RunFlags::new()),
+        }
+    }
+
+    /// The command that opens a URL in the handler for its scheme, falling
+    /// back to `xdg-open` when no handler is known.
+    pub fn open_url(&self, url: &str) -> (Vec<String>, RunFlags) {
+        let mime = match url.split_once(':') {
+            Some(("file", _)) => {
+                let path = url.trim_start_matches("file://");
+                let path = percent_decode_str(path).decode_utf8_lossy();
+                return self.open_file(Path::new(&*path));
+            }
+            None => {
+                return self.open_file(Path::new(url));
+            }
+            Some((scheme, _)) => format!("x-scheme-handler/{}", scheme.to_lowercase()),
+        };
+        self.open_with(&mime, url.to_owned())
+    }
+
+    fn open_with(&self, mime: &str, target: String) -> (Vec<String>, RunFlags) {
+        match self.default_app(mime) {
+            Some(app) => (
+                app.command(&[target]),
+                RunFlags::new().with_term(app.terminal),
+            ),
+            None => (vec!["xdg-open".to_owned(), target], RunFlags::new()),
+        }
+    }
+}
+
+impl AssociationList {
+    fn from_sections(sections: &[Section]) -> Self {
+        let mut retvl = Self::default();
+        for section in sections {
+            let target = match section.header.as_str() {
+                "Default Applications" => &mut retvl.defaults,
+                "Added Associations" => &mut retvl.added,
+                "Removed Associations" => &mut retvl.removed,
+                _ => {
+                    continue;
+                }
+            };
+            for mime in section.fields.keys() {
+                let ids = section.get_field(mime).map(split_list).unwrap_or_default();
+                target.entry(mime.clone()).or_default().extend(ids);
+            }
+        }
+        retvl
+    }
+    fn unaliased(self, types: &MimeDatabase) -> Self {
+        let fix = |map: HashMap<String, Vec<String>>| {
+            let mut retvl: HashMap<String, Vec<String>> = HashMap::new();
+            for (mime, ids) in map {
+                retvl
+                    .entry(types.unalias(&mime).to_owned())
+                    .or_default()
+                    .extend(ids);
+            }
+            retvl
+        };
+        Self {
+            defaults: fix(self.defaults),
+            added: fix(self.added),
+            removed: fix(self.removed),
+        }
+    }
+}
+
+fn split_list(raw: &str) -> Vec<String> {
+    raw.split(';')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_owned())
+        .collect()
+}
+
+/// Every `mimeapps.list` in order of precedence, including the
+/// desktop-specific variants named after `$XDG_CURRENT_DESKTOP`.
+fn mimeapps_paths() -> Vec<PathBuf> {
+    let desktops: Vec<String> = std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_lowercase())
+        .collect();
+    let dirs = xdg_config_home()
+        .into_iter()
+        .chain(xdg_config_dirs())
+        .chain(
+            xdg_data_homes()
+                .chain(xdg_data_dirs())
+                .map(|dir| dir.join("applications")),
+        );
+    let mut retvl = Vec::new();
+    for dir in dirs {
+        for desktop in desktops.iter() {
+            retvl.push(dir.join(format!("{}-mimeapps.list", desktop)));
+        }
+        retvl.push(dir.join("mimeapps.list"));
+    }
+    retvl
+}
+
+impl DesktopApp {
+    fn from_section(path: PathBuf, section: &Section) -> Option<Self> {
+        let is_true = |key: &str| section.get_field(key).is_some_and(|v| v == "true");
+        if is_true("Hidden")
+            || section
+                .get_field("Type")
+                .is_some_and(|t| t != "Application")
+        {
+            return None;
+        }
+        Some(Self {
+            name: section.name(None).unwrap_or_default().to_owned(),
+            exec: section.get_field("Exec")?.to_owned(),
+            icon: section.get_field("Icon").map(|s| s.to_owned()),
+            terminal: section.is_term(),
+            mime_types: section
+                .get_field("MimeType")
+                .map(split_list)
+                .unwrap_or_default(),
+            path,
+        })
+    }
+
+    /// Expands the `Exec` key's field codes for the given files or URLs. If
+    /// the key takes no arguments the targets are appended instead.
+    pub fn command(&self, targets: &[String]) -> Vec<String> {
+        let mut retvl = Vec::new();
+        let mut used_targets = false;
+        for arg in split_exec(&self.exec) {
+            match arg.as_str() {
+                "%f" | "%u" => {
+                    retvl.extend(targets.first().cloned());
+                    used_targets = true;
+                }
+                "%F" | "%U" => {
+                    retvl.extend(targets.iter().cloned());
+                    used_targets = true;
+                }
+                "%i" => {
+                    if let Some(icon) = self.icon.as_ref() {
+                        retvl.push("--icon".to_owned());
+                        retvl.push(icon.clone());
+                    }
+                }
+                _ => {
+                    let (expanded, had_target) = self.expand_codes(&arg, targets);
+                    used_targets |= had_target;
+                    if !expanded.is_empty() || !arg.starts_with('%') {
+                        retvl.push(expanded);
+                    }
+                }
+            }
+        }
+        if !used_targets {
+            retvl.extend(targets.iter().cloned());
+        }
+        retvl
+    }
+
+    fn expand_codes(&self, arg: &str, targets: &[String]) -> (String, bool) {
+        let mut retvl = String::new();
+        let mut had_target = false;
+        let mut chars = arg.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                retvl.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => retvl.push('%'),
+                Some('f') | Some('u') | Some('F') | Some('U') => {
+                    retvl.push_str(&targets.join(" "));
+                    had_target = true;
+                }
+                Some('c') => retvl.push_str(&self.name),
+                Some('k') => retvl.push_str(&self.path.display().to_string()),
+                Some('i') => retvl.push_str(self.icon.as_deref().unwrap_or_default()),
+                // Deprecated and unknown codes are dropped.
+                _ => {}
+            }
+        }
+        (retvl, had_target)
+    }
+}
+
+/// Splits an `Exec` value into arguments following the quoting rules of the
+/// desktop entry specification.
+fn split_exec(raw: &str) -> Vec<String> {
+    let mut retvl = Vec::new();
+    let mut cur: Option<String> = None;
+    let mut in_quotes = false;
+    let mut chars = unescape(raw).into_iter();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                cur.get_or_insert_with(String::new);
+            }
+            '\\' if in_quotes => {
+                if let Some(next) = chars.next() {
+                    cur.get_or_insert_with(String::new).push(next);
+                }
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                retvl.extend(cur.take());
+            }
+            c => {
+                cur.get_or_insert_with(String::new).push(c);
+            }
+        }
+    }
+    retvl.extend(cur);
+    retvl
+}
+
+/// Handles the escapes that apply to every string value in a desktop file.
+fn unescape(raw: &str) -> Vec<char> {
+    let mut retvl = Vec::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            retvl.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => retvl.push(' '),
+            Some('n') => retvl.push('\n'),
+            Some('t') => retvl.push('\t'),
+            Some('r') => retvl.push('\r'),
+            Some('\\') => retvl.push('\\'),
+            Some(other) => retvl.extend(['\\', other].iter()),
+            None => retvl.push('\\'),
+        }
+    }
+    retvl
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(exec: &str, mime_types: &[&str]) -> DesktopApp {
+        DesktopApp {
+            path: PathBuf::from("/usr/share/applications/app.desktop"),
+            name: "App".to_owned(),
+            exec: exec.to_owned(),
+            icon: Some("app-icon".to_owned()),
+            terminal: false,
+            mime_types: mime_types.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn list(
+        defaults: &[(&str, &str)],
+        added: &[(&str, &str)],
+        removed: &[(&str, &str)],
+    ) -> AssociationList {
+        let to_map = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(mime, ids)| (mime.to_string(), split_list(ids)))
+                .collect()
+        };
+        AssociationList {
+            defaults: to_map(defaults),
+            added: to_map(added),
+            removed: to_map(removed),
+        }
+    }
+
+    #[test]
+    fn exec_expansion() {
+        let files = vec!["/tmp/a file.txt".to_owned(), "/tmp/b.txt".to_owned()];
+        assert_eq!(
+            vec!["gedit", "/tmp/a file.txt"],
+            app("gedit %f", &[]).command(&files[..1])
+        );
+        assert_eq!(
+            vec!["gedit", "--new-window", "/tmp/a file.txt", "/tmp/b.txt"],
+            app("gedit --new-window %U", &[]).command(&files)
+        );
+        assert_eq!(
+            vec!["sh", "-c", "echo \"$1\"", "App", "--icon", "app-icon"],
+            app(r#"sh -c "echo \\"\\$1\\"" %c %i %m"#, &[]).command(&[])
+        );
+        assert_eq!(
+            vec!["my app", "--file=/tmp/b.txt", "100%"],
+            app(r#""my app" --file=%f 100%%"#, &[]).command(&files[1..])
+        );
+        assert_eq!(
+            vec!["viewer", "/tmp/b.txt"],
+            app("viewer", &[]).command(&files[1..])
+        );
+    }
+
+    #[test]
+    fn default_resolution() {
+        let apps = vec![
+            ("gedit.desktop".to_owned(), app("gedit %U", &["text/plain"])),
+            (
+                "vim.desktop".to_owned(),
+                app("vim %F", &["text/plain", "text/x-csrc"]),
+            ),
+            (
+                "firefox.desktop".to_owned(),
+                app("firefox %u", &["x-scheme-handler/https"]),
+            ),
+        ];
+        let user = list(
+            &[("text/html", "missing.desktop;firefox.desktop;")],
+            &[],
+            &[("text/plain", "gedit.desktop")],
+        );
+        let system = list(&[], &[("text/plain", "gedit.desktop")], &[]);
+        let mime = MimeApps::from_parts(MimeDatabase::default(), vec![user, system], apps);
+
+        let exec_of = |mime_type: &str| mime.default_app(mime_type).map(|app| app.exec.as_str());
+        assert_eq!(Some("firefox %u"), exec_of("text/html"));
+        assert_eq!(Some("vim %F"), exec_of("text/plain"));
+        assert_eq!(Some("vim %F"), exec_of("text/x-csrc"));
+        assert_eq!(Some("vim %F"), exec_of("text/markdown"));
+        assert_eq!(Some("firefox %u"), exec_of("x-scheme-handler/https"));
+        assert_eq!(None, exec_of("image/png"));
+
+        let (argv, _) = mime.open_url("https://example.com");
+        assert_eq!(vec!["firefox", "https://example.com"], argv);
+        let (argv, _) = mime.open_url("mailto:someone@example.com");
+        assert_eq!(vec!["xdg-open", "mailto:someone@example.com"], argv);
+        let (argv, _) = mime.open_url("file:///tmp/tmpas-missing/My%20Doc.pdf");
+        assert_eq!(vec!["vim", "/tmp/tmpas-missing/My Doc.pdf"], argv);
+    }
+}
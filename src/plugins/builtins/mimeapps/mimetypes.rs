@@ -0,0 +1,467 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+use crate::plugins::builtins::freedesktop::{xdg_data_dirs, xdg_data_homes};
+
+const DIRECTORY: &str = "inode/directory";
+const TEXT: &str = "text/plain";
+const BINARY: &str = "application/octet-stream";
+
+/// The parts of the shared-mime-info database needed to guess the type of a
+/// file: `globs2`, `magic`, `aliases` and `subclasses`.
+#[derive(Debug, Default)]
+pub struct MimeDatabase {
+    globs: Vec<GlobRule>,
+    glob_set: GlobSet,
+    magic: Vec<MagicSection>,
+    magic_extent: usize,
+    aliases: HashMap<String, String>,
+    parents: HashMap<String, Vec<String>>,
+}
+
+#[derive(Debug)]
+struct GlobRule {
+    weight: u32,
+    mime: String,
+    pattern_len: usize,
+    case_sensitive: bool,
+}
+
+#[derive(Debug)]
+struct MagicSection {
+    priority: u32,
+    mime: String,
+    rules: Vec<MagicRule>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct MagicRule {
+    indent: usize,
+    offset: usize,
+    value: Vec<u8>,
+    mask: Option<Vec<u8>>,
+    range: usize,
+}
+
+impl MimeDatabase {
+    pub fn load() -> Self {
+        let mime_dirs: Vec<PathBuf> = xdg_data_homes()
+            .chain(xdg_data_dirs())
+            .map(|dir| dir.join("mime"))
+            .filter(|dir| dir.is_dir())
+            .collect();
+        let mut globs = String::new();
+        let mut aliases = String::new();
+        let mut subclasses = String::new();
+        let mut magic = Vec::new();
+        for dir in mime_dirs.iter() {
+            globs.push_str(&fs::read_to_string(dir.join("globs2")).unwrap_or_default());
+            aliases.push_str(&fs::read_to_string(dir.join("aliases")).unwrap_or_default());
+            subclasses.push_str(&fs::read_to_string(dir.join("subclasses")).unwrap_or_default());
+            if let Ok(raw) = fs::read(dir.join("magic")) {
+                magic.extend(parse_magic(&raw));
+            }
+        }
+        Self::from_parts(&globs, magic, &aliases, &subclasses)
+    }
+
+    fn from_parts(globs: &str, magic: Vec<MagicSection>, aliases: &str, subclasses: &str) -> Self {
+        let (globs, glob_set) = parse_globs(globs);
+        let mut magic = magic;
+        magic.sort_by_key(|section| Reverse(section.priority));
+        let magic_extent = magic
+            .iter()
+            .flat_map(|section| section.rules.iter())
+            .map(|rule| {
+                rule.offset
+                    .saturating_add(rule.range)
+                    .saturating_add(rule.value.len())
+            })
+            .max()
+            .unwrap_or(0)
+            .min(64 * 1024);
+        let aliases = pairs(aliases).collect();
+        let mut parents: HashMap<String, Vec<String>> = HashMap::new();
+        for (child, parent) in pairs(subclasses) {
+            parents.entry(child).or_default().push(parent);
+        }
+        Self {
+            globs,
+            glob_set,
+            magic,
+            magic_extent,
+            aliases,
+            parents,
+        }
+    }
+
+    /// Resolves a MIME type alias like `application/x-pdf` to its canonical
+    /// name.
+    pub fn unalias<'a>(&'a self, mime: &'a str) -> &'a str {
+        self.aliases.get(mime).map_or(mime, |s| s.as_str())
+    }
+
+    /// The types a MIME type is a subclass of, ending in the implicit
+    /// `text/plain` and `application/octet-stream` fallbacks.
+    pub fn parents(&self, mime: &str) -> Vec<String> {
+        let mut retvl: Vec<String> = Vec::new();
+        let mut pending = vec![mime.to_owned()];
+        while let Some(cur) = pending.pop() {
+            for parent in self.parents.get(&cur).into_iter().flatten() {
+                if !retvl.contains(parent) {
+                    retvl.push(parent.clone());
+                    pending.push(parent.clone());
+                }
+            }
+        }
+        if mime.starts_with("text/") && mime != TEXT && !retvl.iter().any(|p| p == TEXT) {
+            retvl.push(TEXT.to_owned());
+        }
+        if !mime.starts_with("inode/") && mime != BINARY && !retvl.iter().any(|p| p == BINARY) {
+            retvl.push(BINARY.to_owned());
+        }
+        retvl
+    }
+
+    /// The type of a file going by its name alone, if that is enough to
+    /// tell. Unlike `guess` this never reads the file.
+    pub fn guess_from_name(&self, path: &Path, is_dir: bool) -> Option<String> {
+        if is_dir {
+            return Some(DIRECTORY.to_owned());
+        }
+        let name = path.file_name()?.to_string_lossy();
+        match self.guess_by_name(&name).as_slice() {
+            [single] => Some(single.clone()),
+            _ => None,
+        }
+    }
+
+    /// Guesses the type of a file, first by its name and then by its contents.
+    pub fn guess(&self, path: &Path) -> String {
+        if path.is_dir() {
+            return DIRECTORY.to_owned();
+        }
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let by_name = self.guess_by_name(&name);
+        if let [single] = by_name.as_slice() {
+            return single.clone();
+        }
+        let data = read_head(path, self.magic_extent);
+        if let Some(mime) = self.guess_by_data(&data, &by_name) {
+            return mime;
+        }
+        if let Some(first) = by_name.into_iter().next() {
+            return first;
+        }
+        if data.contains(&0) || std::str::from_utf8(&data).is_err() {
+            BINARY.to_owned()
+        } else {
+            TEXT.to_owned()
+        }
+    }
+
+    /// All types sharing the best glob match for the given file name.
+    fn guess_by_name(&self, name: &str) -> Vec<String> {
+        let mut matched: Vec<&GlobRule> = self
+            .glob_set
+            .matches(name)
+            .into_iter()
+            .map(|idx| &self.globs[idx])
+            .collect();
+        // Case-sensitive patterns win over case-insensitive ones.
+        if matched.iter().any(|rule| rule.case_sensitive) {
+            matched.retain(|rule| rule.case_sensitive);
+        }
+        matched.sort_by_key(|rule| Reverse((rule.weight, rule.pattern_len)));
+        let best = match matched.first() {
+            Some(rule) => (rule.weight, rule.pattern_len),
+            None => {
+                return Vec::new();
+            }
+        };
+        let mut retvl: Vec<String> = Vec::new();
+        for rule in matched {
+            if (rule.weight, rule.pattern_len) == best && !retvl.contains(&rule.mime) {
+                retvl.push(rule.mime.clone());
+            }
+        }
+        retvl
+    }
+
+    /// Checks the magic rules against the data. When `candidates` is not
+    /// empty only those types are considered.
+    fn guess_by_data(&self, data: &[u8], candidates: &[String]) -> Option<String> {
+        self.magic
+            .iter()
+            .filter(|section| candidates.is_empty() || candidates.contains(&section.mime))
+            .find(|section| rules_match(&section.rules, data))
+            .map(|section| section.mime.clone())
+    }
+}
+
+fn read_head(path: &Path, len: usize) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    if let Ok(file) = File::open(path) {
+        let _ = file.take(len.max(512) as u64).read_to_end(&mut buffer);
+    }
+    buffer
+}
+
+fn pairs(raw: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    raw.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once(' '))
+        .map(|(a, b)| (a.trim().to_owned(), b.trim().to_owned()))
+}
+
+/// Parses `globs2`, whose lines look like `weight:type:glob[:flags]`.
+fn parse_globs(raw: &str) -> (Vec<GlobRule>, GlobSet) {
+    let mut rules = Vec::new();
+    let mut builder = GlobSetBuilder::new();
+    for line in raw.lines().filter(|line| !line.starts_with('#')) {
+        let mut parts = line.splitn(4, ':');
+        let (weight, mime, pattern) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(weight), Some(mime), Some(pattern)) => (weight, mime, pattern),
+            _ => {
+                continue;
+            }
+        };
+        let case_sensitive = parts.next().is_some_and(|flags| flags.contains("cs"));
+        let weight = match weight.parse() {
+            Ok(weight) => weight,
+            Err(_) => {
+                continue;
+            }
+        };
+        let glob = GlobBuilder::new(pattern)
+            .case_insensitive(!case_sensitive)
+            .literal_separator(true)
+            .build();
+        if let Ok(glob) = glob {
+            builder.add(glob);
+            rules.push(GlobRule {
+                weight,
+                mime: mime.to_owned(),
+                pattern_len: pattern.len(),
+                case_sensitive,
+            });
+        }
+    }
+    let set = builder.build().unwrap_or_else(|e| {
+        eprintln!("ERROR: Could not build MIME globs: {}", e);
+        rules.clear();
+        GlobSet::empty()
+    });
+    (rules, set)
+}
+
+/// Parses the binary `magic` file; see the shared-mime-info specification
+/// for the format.
+fn parse_magic(raw: &[u8]) -> Vec<MagicSection> {
+    const HEADER: &[u8] = b"MIME-Magic\0\n";
+    let mut rest = match raw.strip_prefix(HEADER) {
+        Some(rest) => rest,
+        None => {
+            return Vec::new();
+        }
+    };
+    let mut retvl: Vec<MagicSection> = Vec::new();
+    while !rest.is_empty() {
+        if rest[0] == b'[' {
+            let end = match rest.iter().position(|b| *b == b'\n') {
+                Some(end) => end,
+                None => break,
+            };
+            let header = String::from_utf8_lossy(&rest[1..end]);
+            let header = header.trim_end_matches(']');
+            if let Some((priority, mime)) = header.split_once(':') {
+                retvl.push(MagicSection {
+                    priority: priority.parse().unwrap_or(50),
+                    mime: mime.to_owned(),
+                    rules: Vec::new(),
+                });
+            }
+            rest = &rest[end + 1..];
+            continue;
+        }
+        match parse_magic_rule(rest) {
+            Some((rule, remaining)) => {
+                if let Some(section) = retvl.last_mut() {
+                    section.rules.push(rule);
+                }
+                rest = remaining;
+            }
+            None => break,
+        }
+    }
+    retvl
+}
+
+fn parse_magic_rule(raw: &[u8]) -> Option<(MagicRule, &[u8])> {
+    fn number(raw: &[u8]) -> (usize, &[u8]) {
+        let len = raw.iter().take_while(|b| b.is_ascii_digit()).count();
+        let value = std::str::from_utf8(&raw[..len])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(0);
+        (value, &raw[len..])
+    }
+    let (indent, rest) = number(raw);
+    let rest = rest.strip_prefix(b">")?;
+    let (offset, rest) = number(rest);
+    let rest = rest.strip_prefix(b"=")?;
+    let value_len = u16::from_be_bytes([*rest.first()?, *rest.get(1)?]) as usize;
+    let rest = &rest[2..];
+    let value = rest.get(..value_len)?.to_vec();
+    let mut rest = &rest[value_len..];
+    let mut rule = MagicRule {
+        indent,
+        offset,
+        value,
+        mask: None,
+        range: 1,
+    };
+    let mut word_size = 1;
+    while let Some((first, tail)) = rest.split_first() {
+        match first {
+            b'&' => {
+                rule.mask = Some(tail.get(..value_len)?.to_vec());
+                rest = &tail[value_len..];
+            }
+            b'~' => {
+                let (size, tail) = number(tail);
+                word_size = size;
+                rest = tail;
+            }
+            b'+' => {
+                let (range, tail) = number(tail);
+                rule.range = range.max(1);
+                rest = tail;
+            }
+            b'\n' => {
+                rest = tail;
+                break;
+            }
+            _ => {
+                return None;
+            }
+        }
+    }
+    // Multi-byte words are stored big-endian.
+    if cfg!(target_endian = "little") && (word_size == 2 || word_size == 4) {
+        rule.value
+            .chunks_mut(word_size)
+            .for_each(|chunk| chunk.reverse());
+        if let Some(mask) = rule.mask.as_mut() {
+            mask.chunks_mut(word_size).for_each(|chunk| chunk.reverse());
+        }
+    }
+    Some((rule, rest))
+}
+
+/// A rule matches when its own value is found and, if it has nested rules,
+/// at least one of those matches too.
+fn rules_match(rules: &[MagicRule], data: &[u8]) -> bool {
+    let mut idx = 0;
+    while idx < rules.len() {
+        let rule = &rules[idx];
+        let children_end = rules[idx + 1..]
+            .iter()
+            .position(|child| child.indent <= rule.indent)
+            .map_or(rules.len(), |pos| idx + 1 + pos);
+        let children = &rules[idx + 1..children_end];
+        if rule.matches(data) && (children.is_empty() || rules_match(children, data)) {
+            return true;
+        }
+        idx = children_end;
+    }
+    false
+}
+
+impl MagicRule {
+    fn matches(&self, data: &[u8]) -> bool {
+        // No window can start past the end of the data, however large a
+        // malformed rule's range is.
+        let end = self.offset.saturating_add(self.range).min(data.len());
+        (self.offset..end).any(|start| {
+            let window = match start
+                .checked_add(self.value.len())
+                .and_then(|stop| data.get(start..stop))
+            {
+                Some(window) => window,
+                None => {
+                    return false;
+                }
+            };
+            match self.mask.as_ref() {
+                Some(mask) => window
+                    .iter()
+                    .zip(self.value.iter())
+                    .zip(mask.iter())
+                    .all(|((d, v), m)| d & m == v & m),
+                None => window == self.value.as_slice(),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_and_magic() {
+        let globs = "50:text/x-csrc:*.c\n50:text/x-c++src:*.C:cs\n50:image/png:*.png\n\
+                     50:application/gzip:*.gz\n50:application/x-compressed-tar:*.tar.gz\n";
+        let mut magic_raw = b"MIME-Magic\0\n[50:image/png]\n>0=\0\x04\x89PNG\n".to_vec();
+        magic_raw.extend_from_slice(b"[40:text/x-script]\n>0=\0\x02#!\n1>2=\0\x04/bin+3\n");
+        let magic = parse_magic(&magic_raw);
+        let db = MimeDatabase::from_parts(globs, magic, "", "text/x-csrc text/plain\n");
+
+        assert_eq!(vec!["text/x-csrc"], db.guess_by_name("main.c"));
+        assert_eq!(vec!["text/x-c++src"], db.guess_by_name("main.C"));
+        assert_eq!(vec!["image/png"], db.guess_by_name("PHOTO.PNG"));
+        assert_eq!(
+            vec!["application/x-compressed-tar"],
+            db.guess_by_name("archive.tar.gz")
+        );
+        assert!(db.guess_by_name("README").is_empty());
+        assert_eq!(
+            Some("text/x-csrc".to_owned()),
+            db.guess_from_name(Path::new("/src/main.c"), false)
+        );
+        assert_eq!(None, db.guess_from_name(Path::new("/src/README"), false));
+
+        assert_eq!(
+            Some("image/png".to_owned()),
+            db.guess_by_data(b"\x89PNG\r\n", &[])
+        );
+        assert_eq!(
+            Some("text/x-script".to_owned()),
+            db.guess_by_data(b"#! /bin/sh", &[])
+        );
+        assert_eq!(None, db.guess_by_data(b"#!sh", &[]));
+
+        // Offsets and ranges at the limit must neither overflow nor scan
+        // past the data.
+        let mut huge_raw = b"MIME-Magic\0\n[50:x/far]\n>18446744073709551615=\0\x01A+5\n".to_vec();
+        huge_raw.extend_from_slice(b"[40:x/wide]\n>0=\0\x01B+18446744073709551615\n");
+        let db = MimeDatabase::from_parts("", parse_magic(&huge_raw), "", "");
+        assert_eq!(64 * 1024, db.magic_extent);
+        assert_eq!(Some("x/wide".to_owned()), db.guess_by_data(b"AAB", &[]));
+        assert_eq!(None, db.guess_by_data(b"AAA", &[]));
+
+        assert_eq!(
+            vec!["text/plain", "application/octet-stream"],
+            db.parents("text/x-csrc")
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::model::{EntryPlugin, ListEntry, RunFlags};
+use crate::utils::{expand_tilde, home_dir};
+
+/// Prints the value of the first `user:`, `username:` or `login:` line of an
+/// entry, the way browserpass and friends lay them out. The secret is only
+/// ever decrypted by `pass` itself.
+const COPY_USERNAME: &str = r#"pass show "$1" | awk '{ key = tolower($0) } key ~ /^(user|username|login):/ { sub(/^[^:]*: */, ""); print; exit }' | wl-copy -n"#;
+
+pub struct PassPlugin {
+    inner: Box<dyn Iterator<Item = ListEntry>>,
+}
+
+impl PassPlugin {
+    pub fn new() -> Self {
+        Self {
+            inner: Box::new(None.into_iter()),
+        }
+    }
+}
+
+impl EntryPlugin for PassPlugin {
+    fn name(&self) -> String {
+        "Password Store".to_owned()
+    }
+    fn start(&mut self, _config: &Config) {
+        let store = match env::var("PASSWORD_STORE_DIR") {
+            Ok(dir) if !dir.is_empty() => expand_tilde(&dir),
+            _ => match home_dir() {
+                Some(home) => home.join(".password-store"),
+                None => {
+                    return;
+                }
+            },
+        };
+        if !store.is_dir() {
+            return;
+        }
+        let entries: Vec<_> = list_passwords(&store)
+            .into_iter()
+            .map(|name| make_entry(&store, name))
+            .collect();
+        self.inner = Box::new(entries.into_iter());
+    }
+    fn next(&mut self) -> Option<ListEntry> {
+        self.inner.next()
+    }
+}
+
+/// The names `pass` knows the store's entries by: their paths relative to the
+/// store without the `.gpg` extension, sorted.
+fn list_passwords(store: &Path) -> Vec<String> {
+    let mut retvl = Vec::new();
+    let mut queue = vec![store.to_owned()];
+    while let Some(dir) = queue.pop() {
+        let read = match fs::read_dir(&dir) {
+            Ok(read) => read,
+            Err(e) => {
+                eprintln!("ERROR: Could not read {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+        for ent in read.filter_map(|ent| ent.ok()) {
+            let path = ent.path();
+            // Skips `.git`, `.extensions` and the like.
+            if ent.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                queue.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "gpg") {
+                let relative = path.strip_prefix(store).unwrap_or(&path);
+                let name = relative.with_extension("");
+                retvl.push(name.to_string_lossy().into_owned());
+            }
+        }
+    }
+    retvl.sort();
+    retvl
+}
+
+fn make_entry(store: &Path, name: String) -> ListEntry {
+    let pass = |args: &[&str]| -> Vec<String> {
+        let mut command = vec!["pass".to_owned()];
+        command.extend(args.iter().map(|arg| (*arg).to_owned()));
+        command.push(name.clone());
+        command
+    };
+    let children = vec![
+        ListEntry {
+            display_name: Some("Copy username".to_owned()),
+            exec_command: vec![
+                "sh".to_owned(),
+                "-c".to_owned(),
+                COPY_USERNAME.to_owned(),
+                "sh".to_owned(),
+                name.clone(),
+            ],
+            ..Default::default()
+        },
+        ListEntry {
+            display_name: Some("Copy OTP".to_owned()),
+            exec_command: pass(&["otp", "-c"]),
+            ..Default::default()
+        },
+        ListEntry {
+            display_name: Some("Edit in terminal".to_owned()),
+            exec_command: pass(&["edit"]),
+            exec_flags: RunFlags::new().with_term(true),
+            ..Default::default()
+        },
+    ];
+    let mut search_terms = vec!["pass".to_owned()];
+    search_terms.extend(name.rsplit('/').next().map(|leaf| leaf.to_owned()));
+    ListEntry {
+        display_name: Some(name.clone()),
+        search_terms,
+        exec_command: pass(&["show", "-c"]),
+        exec_flags: RunFlags::new(),
+        children,
+        source: Some(store.join(format!("{}.gpg", name))),
+        tag: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    #[test]
+    fn list_store() {
+        let store = TempDir::new("pass");
+        for dir in &["email", "web/shopping", ".git", ".extensions"] {
+            fs::create_dir_all(store.join(dir)).unwrap();
+        }
+        for file in &[
+            ".gpg-id",
+            "wifi.gpg",
+            "email/work.gpg",
+            "email/notes.txt",
+            "web/shopping/example.com.gpg",
+            ".git/config",
+            ".extensions/otp.bash",
+        ] {
+            fs::write(store.join(file), "not actually encrypted").unwrap();
+        }
+        let names = list_passwords(&store);
+        assert_eq!(
+            vec!["email/work", "web/shopping/example.com", "wifi"],
+            names
+        );
+
+        let entry = make_entry(&store, names[1].clone());
+        assert_eq!(
+            vec!["pass", "show", "-c", "web/shopping/example.com"],
+            entry.exec_command
+        );
+        assert_eq!(vec!["pass", "example.com"], entry.search_terms);
+        let children: Vec<_> = entry
+            .children
+            .iter()
+            .map(|child| (child.name(), child.exec_command.last().unwrap().as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("Copy username", "web/shopping/example.com"),
+                ("Copy OTP", "web/shopping/example.com"),
+                ("Edit in terminal", "web/shopping/example.com"),
+            ],
+            children
+        );
+        assert_eq!(
+            vec!["pass", "otp", "-c"],
+            entry.children[1].exec_command[..3]
+        );
+        assert!(entry.children[2].exec_flags.is_term());
+    }
+}
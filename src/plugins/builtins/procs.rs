@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::{getuid, sysconf, Pid, SysconfVar};
+
+use crate::config::Config;
+use crate::model::{next_tag, EntryPlugin, ListEntry, RunFlags, Selected};
+use crate::utils::format_size;
+
+/// The signals offered as actions on every process.
+const ACTIONS: [(&str, Signal); 4] = [
+    ("Terminate", Signal::SIGTERM),
+    ("Kill", Signal::SIGKILL),
+    ("Stop", Signal::SIGSTOP),
+    ("Continue", Signal::SIGCONT),
+];
+
+/// The most processes to list for a single query.
+const LIMIT: usize = 50;
+
+/// The process and signal behind each tagged entry.
+type Signals = HashMap<u64, (i32, Signal)>;
+
+pub struct ProcsPlugin {
+    /// The entries of the latest query.
+    queried: Signals,
+    /// The entries of the query before, which the UI may still be showing.
+    previous: Signals,
+}
+
+impl ProcsPlugin {
+    pub fn new() -> Self {
+        Self {
+            queried: Signals::new(),
+            previous: Signals::new(),
+        }
+    }
+}
+
+impl EntryPlugin for ProcsPlugin {
+    fn name(&self) -> String {
+        "Processes".to_owned()
+    }
+    fn start(&mut self, _config: &Config) {}
+    fn next(&mut self) -> Option<ListEntry> {
+        None
+    }
+    fn query(&mut self, text: &str) -> Vec<ListEntry> {
+        let trimmed = text.trim();
+        let (keyword, filter) = trimmed.split_once(' ').unwrap_or((trimmed, ""));
+        if keyword != "ps" && keyword != "kill" {
+            return Vec::new();
+        }
+        self.previous = std::mem::take(&mut self.queried);
+        let filter = filter.trim().to_lowercase();
+        // Read fresh on every query, since the numbers change all the time.
+        let mut procs: Vec<Process> = list_processes()
+            .into_iter()
+            .filter(|proc| proc.matches(&filter))
+            .collect();
+        procs.sort_by(|a, b| b.cpu.total_cmp(&a.cpu));
+        procs
+            .into_iter()
+            .take(LIMIT)
+            .map(|proc| proc.into_entry(text, &mut self.queried))
+            .collect()
+    }
+    fn caches_queries(&self) -> bool {
+        false
+    }
+    fn select(&mut self, entry: &ListEntry) -> Option<Selected> {
+        let tag = entry.tag?;
+        let (pid, signal) = *self.queried.get(&tag).or_else(|| self.previous.get(&tag))?;
+        let msg = match kill(Pid::from_raw(pid), signal) {
+            Ok(()) => format!("Sent {} to process {}", signal.as_str(), pid),
+            Err(e) => format!(
+                "Failed to send {} to process {}: {}",
+                signal.as_str(),
+                pid,
+                e
+            ),
+        };
+        Some(Selected::Done(msg))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Process {
+    pid: i32,
+    name: String,
+    command: String,
+    /// Average CPU usage over the lifetime of the process, in percent.
+    cpu: f64,
+    /// Resident memory in bytes.
+    rss: u64,
+}
+
+impl Process {
+    fn matches(&self, filter: &str) -> bool {
+        filter.is_empty()
+            || self.name.to_lowercase().contains(filter)
+            || self.command.to_lowercase().contains(filter)
+            || self.pid.to_string() == filter
+    }
+
+    /// Remembers the signal of every entry it makes in `signals`.
+    fn into_entry(self, query: &str, signals: &mut Signals) -> ListEntry {
+        let pid = self.pid;
+        let mut tagged = |signal: Signal| {
+            let tag = next_tag();
+            signals.insert(tag, (pid, signal));
+            Some(tag)
+        };
+        let cpu = format!("{:.1}% CPU", self.cpu);
+        let rss = format!("{} RSS", format_size(self.rss));
+        let children = ACTIONS
+            .iter()
+            .map(|(label, signal)| ListEntry {
+                display_name: Some(format!("{} ({})", label, signal.as_str())),
+                exec_command: kill_command(self.pid, *signal),
+                exec_flags: RunFlags::new()
+                    .with_plugin_handled(true)
+                    .with_keep_open(true),
+                tag: tagged(*signal),
+                ..Default::default()
+            })
+            .collect();
+        ListEntry {
+            display_name: Some(format!("{} ({})  {}  {}", self.name, self.pid, cpu, rss)),
+            search_terms: vec![
+                query.to_owned(),
+                self.name,
+                self.pid.to_string(),
+                self.command,
+                cpu,
+                rss,
+            ],
+            exec_command: kill_command(self.pid, Signal::SIGTERM),
+            exec_flags: RunFlags::new()
+                .with_plugin_handled(true)
+                .with_keep_open(true),
+            children,
+            source: None,
+            tag: tagged(Signal::SIGTERM),
+        }
+    }
+}
+
+/// The `kill` command equivalent to sending the signal, for "Copy command".
+fn kill_command(pid: i32, signal: Signal) -> Vec<String> {
+    let name = signal.as_str().trim_start_matches("SIG");
+    vec![
+        "kill".to_owned(),
+        "-s".to_owned(),
+        name.to_owned(),
+        pid.to_string(),
+    ]
+}
+
+/// The fields of `/proc/<pid>/stat` we care about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Stat {
+    name: String,
+    state: char,
+    /// User plus system time, in clock ticks.
+    cpu_ticks: u64,
+    /// Clock ticks between boot and the process starting.
+    start_ticks: u64,
+    rss_pages: u64,
+}
+
+/// Parses a `/proc/<pid>/stat` line. The name is wrapped in parentheses but
+/// may itself contain spaces and parentheses, so the remaining fields start
+/// after the last `)`.
+fn parse_stat(raw: &str) -> Option<Stat> {
+    let open = raw.find('(')?;
+    let close = raw.rfind(')')?;
+    let name = raw.get(open + 1..close)?.to_owned();
+    // Indexed from the state, which is the third field.
+    let fields: Vec<&str> = raw[close + 1..].split_whitespace().collect();
+    let field = |idx: usize| -> Option<u64> { fields.get(idx - 3)?.parse().ok() };
+    Some(Stat {
+        name,
+        state: fields.first()?.chars().next()?,
+        cpu_ticks: field(14)? + field(15)?,
+        start_ticks: field(22)?,
+        rss_pages: field(24)?,
+    })
+}
+
+/// Every live process owned by the current user.
+fn list_processes() -> Vec<Process> {
+    let uid = getuid().as_raw();
+    let clock_ticks = sysconf(SysconfVar::CLK_TCK).ok().flatten().unwrap_or(100) as f64;
+    let page_size = sysconf(SysconfVar::PAGE_SIZE)
+        .ok()
+        .flatten()
+        .unwrap_or(4096) as u64;
+    let uptime: f64 = match fs::read_to_string("/proc/uptime") {
+        Ok(raw) => raw
+            .split_whitespace()
+            .next()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or_default(),
+        Err(e) => {
+            eprintln!("ERROR: Could not read /proc/uptime: {}", e);
+            return Vec::new();
+        }
+    };
+    let dir = match fs::read_dir("/proc") {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("ERROR: Could not read /proc: {}", e);
+            return Vec::new();
+        }
+    };
+    // Processes can exit at any point while we read them, so any failure
+    // below just means skipping that process.
+    dir.filter_map(|ent| ent.ok())
+        .filter_map(|ent| {
+            let pid: i32 = ent.file_name().to_str()?.parse().ok()?;
+            let path = ent.path();
+            if ent.metadata().ok()?.uid() != uid {
+                return None;
+            }
+            let stat = parse_stat(&fs::read_to_string(path.join("stat")).ok()?)?;
+            let command = read_cmdline(&path)?;
+            // Kernel threads have no command line, and zombies cannot be
+            // signalled.
+            if command.is_empty() || stat.state == 'Z' {
+                return None;
+            }
+            let elapsed = uptime - stat.start_ticks as f64 / clock_ticks;
+            let cpu = if elapsed > 0.0 {
+                100.0 * stat.cpu_ticks as f64 / clock_ticks / elapsed
+            } else {
+                0.0
+            };
+            Some(Process {
+                pid,
+                name: stat.name,
+                command,
+                cpu,
+                rss: stat.rss_pages * page_size,
+            })
+        })
+        .collect()
+}
+
+fn read_cmdline(proc_dir: &Path) -> Option<String> {
+    let raw = fs::read(proc_dir.join("cmdline")).ok()?;
+    let args: Vec<_> = raw
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(String::from_utf8_lossy)
+        .collect();
+    Some(args.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stat_and_signals() {
+        let raw = "4321 (Web (Content)) S 4300 4300 4300 0 -1 4194560 8812 0 0 0 \
+                   150 50 0 0 20 0 31 0 12000 2714406912 51200 18446744073709551615";
+        let stat = parse_stat(raw).unwrap();
+        assert_eq!(
+            Stat {
+                name: "Web (Content)".to_owned(),
+                state: 'S',
+                cpu_ticks: 200,
+                start_ticks: 12000,
+                rss_pages: 51200,
+            },
+            stat
+        );
+        assert_eq!(None, parse_stat("4321 (truncated) S 1 2"));
+
+        let command = kill_command(4321, Signal::SIGSTOP);
+        assert_eq!(vec!["kill", "-s", "STOP", "4321"], command);
+
+        let mut plugin = ProcsPlugin::new();
+        let proc = Process {
+            pid: 4321,
+            name: "firefox".to_owned(),
+            command: "/usr/lib/firefox/firefox".to_owned(),
+            cpu: 2.5,
+            rss: 4096,
+        };
+        let entry = proc.into_entry("ps fire", &mut plugin.queried);
+        let stop = &entry.children[2];
+        assert_eq!(command, stop.exec_command);
+        assert_eq!(
+            Some(&(4321, Signal::SIGSTOP)),
+            plugin.queried.get(&stop.tag.unwrap())
+        );
+        // The same command from anywhere else is not ours to run.
+        let untagged = ListEntry {
+            tag: None,
+            ..stop.clone()
+        };
+        assert_eq!(None, plugin.select(&untagged));
+    }
+}
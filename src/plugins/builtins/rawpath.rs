@@ -51,6 +51,8 @@ fn make_entry(raw_path: impl AsRef<Path>) -> ListEntry {
         exec_flags: RunFlags::new(),
         search_terms: Vec::new(),
         children: Vec::new(),
+        source: None,
+        tag: None,
     }
 }
 
@@ -0,0 +1,201 @@
+use std::fs;
+use std::path::PathBuf;
+
+use percent_encoding::percent_decode_str;
+
+use crate::config::Config;
+use crate::model::{EntryPlugin, ListEntry};
+use crate::plugins::builtins::freedesktop::xdg_data_homes;
+use crate::plugins::builtins::mimeapps::{DesktopApp, MimeApps};
+use crate::utils::contract_tilde;
+
+const BOOKMARK_NS: &str = "http://www.freedesktop.org/standards/desktop-bookmarks";
+
+pub struct RecentPlugin {
+    inner: Box<dyn Iterator<Item = ListEntry>>,
+}
+
+impl RecentPlugin {
+    pub fn new() -> Self {
+        Self {
+            inner: Box::new(None.into_iter()),
+        }
+    }
+}
+
+impl EntryPlugin for RecentPlugin {
+    fn name(&self) -> String {
+        "Recent Files".to_owned()
+    }
+    fn start(&mut self, _config: &Config) {
+        let path = match xdg_data_homes().next() {
+            Some(data_home) => data_home.join("recently-used.xbel"),
+            None => {
+                return;
+            }
+        };
+        let raw = match fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(e) => {
+                eprintln!("ERROR: Could not read {}: {}", path.display(), e);
+                return;
+            }
+        };
+        let mut bookmarks = match parse_xbel(&raw) {
+            Ok(bookmarks) => bookmarks,
+            Err(e) => {
+                eprintln!("ERROR: Could not parse {}: {}", path.display(), e);
+                return;
+            }
+        };
+        bookmarks.retain(|bookmark| bookmark.path.exists());
+        let mime = MimeApps::load();
+        let entries: Vec<_> = bookmarks
+            .into_iter()
+            .map(|bookmark| bookmark.into_entry(&mime))
+            .collect();
+        self.inner = Box::new(entries.into_iter());
+    }
+    fn next(&mut self) -> Option<ListEntry> {
+        self.inner.next()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bookmark {
+    path: PathBuf,
+    visited: String,
+    /// Applications that registered the file, as `(name, exec)` pairs.
+    applications: Vec<(String, String)>,
+}
+
+impl Bookmark {
+    fn into_entry(self, mime: &MimeApps) -> ListEntry {
+        let (exec_command, exec_flags) = mime.open_file(&self.path);
+        let target = self.path.display().to_string();
+        let file_name = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| target.clone());
+        let mut search_terms = vec![contract_tilde(&self.path)];
+        search_terms.extend(self.applications.iter().map(|(name, _)| name.clone()));
+        let children = self
+            .applications
+            .iter()
+            .map(|(name, exec)| {
+                let app = DesktopApp {
+                    name: name.clone(),
+                    exec: exec.clone(),
+                    ..DesktopApp::default()
+                };
+                ListEntry {
+                    display_name: Some(format!("Open with {}", name)),
+                    exec_command: app.command(std::slice::from_ref(&target)),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        ListEntry {
+            display_name: Some(file_name),
+            search_terms,
+            exec_command,
+            exec_flags,
+            children,
+            source: Some(self.path),
+            tag: None,
+        }
+    }
+}
+
+/// Reads the local files out of an XBEL document, most recently visited
+/// first.
+fn parse_xbel(raw: &str) -> Result<Vec<Bookmark>, roxmltree::Error> {
+    let doc = roxmltree::Document::parse(raw)?;
+    let mut retvl: Vec<Bookmark> = doc
+        .root_element()
+        .children()
+        .filter(|node| node.has_tag_name("bookmark"))
+        .filter_map(|node| {
+            let href = node.attribute("href")?;
+            let path = href.strip_prefix("file://")?;
+            let path = percent_decode_str(path).decode_utf8().ok()?;
+            let visited = ["visited", "modified", "added"]
+                .iter()
+                .find_map(|attr| node.attribute(*attr))
+                .unwrap_or_default();
+            let applications = node
+                .descendants()
+                .filter(|node| node.has_tag_name((BOOKMARK_NS, "application")))
+                .filter_map(|app| {
+                    let name = app.attribute("name")?;
+                    let exec = app.attribute("exec")?;
+                    // GTK wraps the command in single quotes.
+                    let exec = exec.trim_matches('\'');
+                    Some((name.to_owned(), exec.to_owned()))
+                })
+                .collect();
+            Some(Bookmark {
+                path: PathBuf::from(path.into_owned()),
+                visited: visited.to_owned(),
+                applications,
+            })
+        })
+        .collect();
+    // The timestamps are all ISO 8601, so they sort as plain strings.
+    retvl.sort_by(|a, b| b.visited.cmp(&a.visited));
+    Ok(retvl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XBEL: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xbel version="1.0"
+      xmlns:bookmark="http://www.freedesktop.org/standards/desktop-bookmarks"
+      xmlns:mime="http://www.freedesktop.org/standards/shared-mime-info">
+  <bookmark href="file:///home/me/notes.txt" added="2021-01-01T10:00:00Z" modified="2021-01-01T10:00:00Z" visited="2021-01-02T09:00:00.123Z">
+    <info>
+      <metadata owner="http://freedesktop.org">
+        <mime:mime-type type="text/plain"/>
+        <bookmark:applications>
+          <bookmark:application name="gedit" exec="&apos;gedit %u&apos;" modified="2021-01-02T09:00:00Z" count="2"/>
+          <bookmark:application name="Text Editor" exec="&apos;gnome-text-editor %u&apos;" modified="2021-01-01T10:00:00Z" count="1"/>
+        </bookmark:applications>
+      </metadata>
+    </info>
+  </bookmark>
+  <bookmark href="file:///home/me/My%20Photo.png" added="2021-01-03T10:00:00Z" modified="2021-01-03T10:00:00Z" visited="2021-01-03T10:00:00Z"/>
+  <bookmark href="https://example.com/" added="2021-01-04T10:00:00Z" visited="2021-01-04T10:00:00Z"/>
+</xbel>
+"#;
+
+    #[test]
+    fn parse_bookmarks() {
+        let bookmarks = parse_xbel(XBEL).unwrap();
+        assert_eq!(2, bookmarks.len());
+        assert_eq!(PathBuf::from("/home/me/My Photo.png"), bookmarks[0].path);
+        assert!(bookmarks[0].applications.is_empty());
+
+        let entry = bookmarks[1].clone().into_entry(&MimeApps::default());
+        assert_eq!("notes.txt", entry.name());
+        assert_eq!(vec!["xdg-open", "/home/me/notes.txt"], entry.exec_command);
+        assert_eq!(vec!["gedit", "Text Editor"], entry.search_terms[1..]);
+        let children: Vec<_> = entry
+            .children
+            .iter()
+            .map(|child| (child.name(), child.exec_command.join(" ")))
+            .collect();
+        assert_eq!(
+            vec![
+                ("Open with gedit", "gedit /home/me/notes.txt".to_owned()),
+                (
+                    "Open with Text Editor",
+                    "gnome-text-editor /home/me/notes.txt".to_owned()
+                ),
+            ],
+            children
+        );
+    }
+}
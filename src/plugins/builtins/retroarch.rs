@@ -0,0 +1,561 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::model::{EntryPlugin, ListEntry};
+use crate::plugins::builtins::freedesktop::xdg_config_home;
+use crate::utils::{expand_tilde, home_dir};
+
+/// Extensions of archives, which any core could be asked to open, so they are
+/// matched to cores by system instead.
+const ARCHIVES: &[&str] = &["zip", "7z"];
+
+/// The `[retroarch]` table of the config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetroArchConfig {
+    /// The command used to start RetroArch, before the `-L <core> <rom>`
+    /// arguments.
+    pub command: Vec<String>,
+    /// Path to `retroarch.cfg`. By default the native and then the Flatpak
+    /// config are tried.
+    pub config: Option<String>,
+    /// Directories holding one subdirectory of ROMs per system, named after
+    /// the system's `systemid`.
+    pub rom_roots: Vec<String>,
+    /// The core to launch by default, by system. Systems are ROM directory
+    /// names or playlist names, and cores either the core file name without
+    /// extension or its display name.
+    pub preferred_cores: HashMap<String, String>,
+    /// Systems whose cores can also run archives from another system's
+    /// directory.
+    pub related_systems: HashMap<String, Vec<String>>,
+}
+
+impl Default for RetroArchConfig {
+    fn default() -> Self {
+        let related = |systems: &[&str]| systems.iter().map(|s| (*s).to_owned()).collect();
+        let mut related_systems = HashMap::new();
+        related_systems.insert("mame".to_owned(), related(&["fb_alpha", "fbneo"]));
+        related_systems.insert("fb_alpha".to_owned(), related(&["mame", "fbneo"]));
+        related_systems.insert("fbneo".to_owned(), related(&["mame", "fb_alpha"]));
+        Self {
+            command: vec!["retroarch".to_owned()],
+            config: None,
+            rom_roots: vec!["~/roms".to_owned()],
+            preferred_cores: HashMap::new(),
+            related_systems,
+        }
+    }
+}
+
+pub struct RetroArchPlugin {
+    inner: Box<dyn Iterator<Item = ListEntry>>,
+}
+
+impl RetroArchPlugin {
+    pub fn new() -> Self {
+        Self {
+            inner: Box::new(None.into_iter()),
+        }
+    }
+}
+
+impl EntryPlugin for RetroArchPlugin {
+    fn name(&self) -> String {
+        "RetroArch".to_owned()
+    }
+    fn start(&mut self, config: &Config) {
+        let config = &config.retroarch;
+        let cfg_path = match config.config.as_ref() {
+            Some(path) => expand_tilde(path),
+            None => match default_config_path() {
+                Some(path) => path,
+                None => {
+                    return;
+                }
+            },
+        };
+        let retroarch = match RetroArch::load(&cfg_path) {
+            Some(retroarch) => retroarch,
+            None => {
+                return;
+            }
+        };
+        let roots: Vec<PathBuf> = config
+            .rom_roots
+            .iter()
+            .map(|root| expand_tilde(root))
+            .collect();
+        let entries = retroarch.entries(config, &roots);
+        self.inner = Box::new(entries.into_iter());
+    }
+    fn next(&mut self) -> Option<ListEntry> {
+        self.inner.next()
+    }
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    let native = xdg_config_home().map(|dir| dir.join("retroarch/retroarch.cfg"));
+    let flatpak = home_dir()
+        .map(|home| home.join(".var/app/org.libretro.RetroArch/config/retroarch/retroarch.cfg"));
+    native
+        .into_iter()
+        .chain(flatpak)
+        .find(|path| path.is_file())
+}
+
+/// Parses the `key = "value"` lines shared by `retroarch.cfg` and core `.info`
+/// files.
+fn parse_cfg(raw: &str) -> HashMap<String, String> {
+    raw.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            Some((key.trim().to_owned(), value.to_owned()))
+        })
+        .collect()
+}
+
+fn read_cfg(path: &Path) -> Option<HashMap<String, String>> {
+    match fs::read_to_string(path) {
+        Ok(raw) => Some(parse_cfg(&raw)),
+        Err(e) => {
+            eprintln!("ERROR: Could not read {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Core {
+    /// The core's file name without extension, like `snes9x_libretro`.
+    name: String,
+    path: PathBuf,
+    display_name: String,
+    systemid: Option<String>,
+    extensions: Vec<String>,
+    /// The database names, which playlists are named after.
+    databases: Vec<String>,
+}
+
+impl Core {
+    fn load(path: PathBuf, info_dir: &Path) -> Self {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let info_path = info_dir.join(format!("{}.info", name));
+        let info = if info_path.is_file() {
+            read_cfg(&info_path).unwrap_or_default()
+        } else {
+            HashMap::new()
+        };
+        let split = |key: &str| -> Vec<String> {
+            info.get(key)
+                .map(|raw| {
+                    raw.split('|')
+                        .filter(|part| !part.is_empty())
+                        .map(str::to_owned)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        Self {
+            display_name: info
+                .get("display_name")
+                .cloned()
+                .unwrap_or_else(|| name.clone()),
+            systemid: info.get("systemid").cloned(),
+            extensions: split("supported_extensions"),
+            databases: split("database"),
+            name,
+            path,
+        }
+    }
+
+    fn is_for(&self, systems: &[&str]) -> bool {
+        self.systemid
+            .as_deref()
+            .is_some_and(|id| systems.contains(&id))
+            || self
+                .databases
+                .iter()
+                .any(|db| systems.contains(&db.as_str()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rom {
+    name: String,
+    path: String,
+    /// The ROM directory or playlist this came from.
+    system: String,
+    /// The core path a playlist asks for, if any.
+    core_path: Option<PathBuf>,
+}
+
+impl Rom {
+    fn extension(&self) -> String {
+        // Playlists point into archives as `archive.zip#game.sfc`.
+        let file = self.path.rsplit('#').next().unwrap_or(&self.path);
+        Path::new(file)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+}
+
+struct RetroArch {
+    cores: Vec<Core>,
+    playlist_dir: PathBuf,
+}
+
+impl RetroArch {
+    fn load(cfg_path: &Path) -> Option<Self> {
+        let cfg = read_cfg(cfg_path)?;
+        let cfg_dir = cfg_path.parent().unwrap_or_else(|| Path::new("."));
+        let dir = |key: &str, default: &str| match cfg.get(key) {
+            Some(value) if !value.is_empty() && value != "default" => expand_tilde(value),
+            _ => cfg_dir.join(default),
+        };
+        let core_dir = dir("libretro_directory", "cores");
+        let info_dir = dir("libretro_info_path", "cores");
+        let playlist_dir = dir("playlist_directory", "playlists");
+        let mut cores: Vec<Core> = match fs::read_dir(&core_dir) {
+            Ok(read) => read
+                .filter_map(|ent| ent.ok())
+                .map(|ent| ent.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "so"))
+                .map(|path| Core::load(path, &info_dir))
+                .collect(),
+            Err(e) => {
+                eprintln!("ERROR: Could not read {}: {}", core_dir.display(), e);
+                Vec::new()
+            }
+        };
+        cores.sort_by(|a, b| a.name.cmp(&b.name));
+        Some(Self {
+            cores,
+            playlist_dir,
+        })
+    }
+
+    fn entries(&self, config: &RetroArchConfig, roots: &[PathBuf]) -> Vec<ListEntry> {
+        let mut roms = read_playlists(&self.playlist_dir);
+        for root in roots {
+            roms.extend(read_rom_root(root));
+        }
+        let mut seen = HashSet::new();
+        roms.into_iter()
+            .filter(|rom| seen.insert(rom.path.clone()))
+            .filter_map(|rom| {
+                let cores = self.cores_for(&rom, config);
+                make_entry(rom, &cores, &config.command)
+            })
+            .collect()
+    }
+
+    /// The cores that can run a ROM, best first.
+    fn cores_for(&self, rom: &Rom, config: &RetroArchConfig) -> Vec<&Core> {
+        let ext = rom.extension();
+        let mut systems = vec![rom.system.as_str()];
+        if let Some(related) = config.related_systems.get(&rom.system) {
+            systems.extend(related.iter().map(String::as_str));
+        }
+        let mut retvl: Vec<&Core> = if ARCHIVES.contains(&ext.as_str()) {
+            self.cores
+                .iter()
+                .filter(|core| core.is_for(&systems))
+                .collect()
+        } else {
+            self.cores
+                .iter()
+                .filter(|core| core.extensions.contains(&ext))
+                .collect()
+        };
+        // Cores made for the ROM's own system come first, then the preferred
+        // core for it, then whatever a playlist asked for. Sorting is stable,
+        // so each step keeps the order of the ones before.
+        retvl.sort_by_key(|core| !core.is_for(&systems[..1]));
+        if let Some(preferred) = config.preferred_cores.get(&rom.system) {
+            retvl.sort_by_key(|core| core.name != *preferred && core.display_name != *preferred);
+        }
+        if let Some(core_path) = rom.core_path.as_ref() {
+            match self.cores.iter().find(|core| core.path == *core_path) {
+                Some(core) => {
+                    retvl.retain(|other| other.path != core.path);
+                    retvl.insert(0, core);
+                }
+                None => {
+                    eprintln!("WARNING: Playlist core {} not found.", core_path.display());
+                }
+            }
+        }
+        retvl
+    }
+}
+
+fn make_entry(rom: Rom, cores: &[&Core], command: &[String]) -> Option<ListEntry> {
+    let first = cores.first()?;
+    let run = |core: &Core| {
+        let mut argv = command.to_vec();
+        argv.push("-L".to_owned());
+        argv.push(core.path.display().to_string());
+        argv.push(rom.path.clone());
+        argv
+    };
+    let ext = rom.extension();
+    let mut search_terms = vec![ext.clone(), rom.system.clone()];
+    search_terms.extend(cores.iter().map(|core| core.display_name.clone()));
+    let children = if cores.len() > 1 {
+        cores
+            .iter()
+            .map(|core| ListEntry {
+                display_name: Some(core.display_name.clone()),
+                search_terms: vec![rom.name.clone(), ext.clone(), rom.system.clone()],
+                exec_command: run(core),
+                ..Default::default()
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    Some(ListEntry {
+        exec_command: run(first),
+        display_name: Some(rom.name),
+        search_terms,
+        children,
+        source: Some(PathBuf::from(rom.path)),
+        tag: None,
+        ..Default::default()
+    })
+}
+
+/// Reads `<root>/<system>/<rom>` files, skipping hidden ones.
+fn read_rom_root(root: &Path) -> Vec<Rom> {
+    let mut retvl = Vec::new();
+    let systems = match fs::read_dir(root) {
+        Ok(systems) => systems,
+        Err(e) => {
+            eprintln!("ERROR: Could not read {}: {}", root.display(), e);
+            return retvl;
+        }
+    };
+    let visible = |ent: &fs::DirEntry| !ent.file_name().to_string_lossy().starts_with('.');
+    for system in systems.filter_map(|ent| ent.ok()).filter(visible) {
+        let system_name = system.file_name().to_string_lossy().into_owned();
+        let files = match fs::read_dir(system.path()) {
+            Ok(files) => files,
+            Err(_) => continue,
+        };
+        for file in files.filter_map(|ent| ent.ok()).filter(visible) {
+            let path = file.path();
+            if !path.is_file() {
+                continue;
+            }
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            retvl.push(Rom {
+                name,
+                path: path.display().to_string(),
+                system: system_name.clone(),
+                core_path: None,
+            });
+        }
+    }
+    retvl.sort_by(|a, b| (&a.system, &a.name).cmp(&(&b.system, &b.name)));
+    retvl
+}
+
+/// Reads every JSON `.lpl` playlist in the directory. Each playlist is named
+/// after the database of the system it holds.
+fn read_playlists(dir: &Path) -> Vec<Rom> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(read) => read
+            .filter_map(|ent| ent.ok())
+            .map(|ent| ent.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "lpl"))
+            .collect(),
+        Err(_) => {
+            return Vec::new();
+        }
+    };
+    paths.sort();
+    let mut retvl = Vec::new();
+    for path in paths {
+        let raw = match fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(e) => {
+                eprintln!("ERROR: Could not read {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let system = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match parse_playlist(&raw, &system) {
+            Some(roms) => retvl.extend(roms),
+            None => eprintln!("ERROR: Could not parse playlist {}", path.display()),
+        }
+    }
+    retvl
+}
+
+fn parse_playlist(raw: &str, system: &str) -> Option<Vec<Rom>> {
+    let doc: serde_json::Value = serde_json::from_str(raw).ok()?;
+    let items = doc.get("items")?.as_array()?;
+    let field = |item: &serde_json::Value, key: &str| -> Option<String> {
+        let value = item.get(key)?.as_str()?;
+        if value.is_empty() || value == "DETECT" {
+            None
+        } else {
+            Some(value.to_owned())
+        }
+    };
+    let retvl = items
+        .iter()
+        .filter_map(|item| {
+            let path = field(item, "path")?;
+            let name = field(item, "label").unwrap_or_else(|| {
+                let file = path.rsplit(['/', '#']).next().unwrap_or(&path);
+                Path::new(file)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            });
+            let system = field(item, "db_name")
+                .map(|db| db.trim_end_matches(".lpl").to_owned())
+                .unwrap_or_else(|| system.to_owned());
+            Some(Rom {
+                name,
+                path,
+                system,
+                core_path: field(item, "core_path").map(PathBuf::from),
+            })
+        })
+        .collect();
+    Some(retvl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    #[test]
+    fn cores_and_roms() {
+        let base = TempDir::new("retroarch");
+        for dir in &["cores", "info", "playlists", "roms/snes", "roms/mame"] {
+            fs::create_dir_all(base.join(dir)).unwrap();
+        }
+        let files = [
+            (
+                "retroarch.cfg",
+                format!(
+                    "# Generated\nlibretro_directory = \"{0}/cores\"\nlibretro_info_path = \"{0}/info\"\nplaylist_directory = \"default\"\n",
+                    base.display()
+                ),
+            ),
+            ("cores/snes9x_libretro.so", String::new()),
+            ("cores/bsnes_libretro.so", String::new()),
+            ("cores/mame_libretro.so", String::new()),
+            ("cores/fbneo_libretro.so", String::new()),
+            (
+                "info/snes9x_libretro.info",
+                "display_name = \"Nintendo - SNES / SFC (Snes9x - Current)\"\nsupported_extensions = \"smc|sfc|swc|fig|bs|st\"\nsystemid = \"super_nes\"\ndatabase = \"Nintendo - Super Nintendo Entertainment System\"\n".to_owned(),
+            ),
+            (
+                "info/bsnes_libretro.info",
+                "display_name = \"Nintendo - SNES / SFC (bsnes)\"\nsupported_extensions = \"sfc|smc|gb|gbc|bs\"\nsystemid = \"super_nes\"\n".to_owned(),
+            ),
+            (
+                "info/mame_libretro.info",
+                "display_name = \"Arcade (MAME)\"\nsupported_extensions = \"zip|chd|7z|cmd\"\nsystemid = \"mame\"\n".to_owned(),
+            ),
+            (
+                "info/fbneo_libretro.info",
+                "display_name = \"Arcade (FinalBurn Neo)\"\nsupported_extensions = \"zip|7z\"\nsystemid = \"fbneo\"\n".to_owned(),
+            ),
+            ("roms/snes/Super Metroid.sfc", String::new()),
+            ("roms/snes/notes.txt", String::new()),
+            ("roms/mame/pacman.zip", String::new()),
+            (
+                "playlists/Nintendo - Super Nintendo Entertainment System.lpl",
+                format!(
+                    r#"{{"version": "1.5", "items": [
+                        {{"path": "{0}/roms/snes/Super Metroid.sfc", "label": "Super Metroid (Japan, USA)", "core_path": "DETECT", "core_name": "DETECT", "db_name": "Nintendo - Super Nintendo Entertainment System.lpl"}},
+                        {{"path": "/elsewhere/Chrono Trigger.smc", "label": "Chrono Trigger", "core_path": "{0}/cores/bsnes_libretro.so", "core_name": "bsnes"}}
+                    ]}}"#,
+                    base.display()
+                ),
+            ),
+        ];
+        for (file, contents) in files.iter() {
+            fs::write(base.join(file), contents).unwrap();
+        }
+        let mut config = RetroArchConfig::default();
+        config
+            .preferred_cores
+            .insert("mame".to_owned(), "Arcade (FinalBurn Neo)".to_owned());
+        let retroarch = RetroArch::load(&base.join("retroarch.cfg")).unwrap();
+        let entries = retroarch.entries(&config, &[base.join("roms")]);
+
+        let core = |name: &str| format!("{}/cores/{}_libretro.so", base.display(), name);
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|ent| {
+                let children: Vec<_> = ent.children.iter().map(|child| child.name()).collect();
+                (ent.name(), ent.exec_command[2].clone(), children)
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    "Super Metroid (Japan, USA)",
+                    core("snes9x"),
+                    vec![
+                        "Nintendo - SNES / SFC (Snes9x - Current)",
+                        "Nintendo - SNES / SFC (bsnes)"
+                    ]
+                ),
+                (
+                    "Chrono Trigger",
+                    core("bsnes"),
+                    vec![
+                        "Nintendo - SNES / SFC (bsnes)",
+                        "Nintendo - SNES / SFC (Snes9x - Current)"
+                    ]
+                ),
+                (
+                    "pacman",
+                    core("fbneo"),
+                    vec!["Arcade (FinalBurn Neo)", "Arcade (MAME)"]
+                ),
+            ],
+            summary
+        );
+        assert_eq!(
+            vec![
+                "retroarch".to_owned(),
+                "-L".to_owned(),
+                core("fbneo"),
+                format!("{}/roms/mame/pacman.zip", base.display())
+            ],
+            entries[2].exec_command
+        );
+        assert_eq!(vec!["zip", "mame"], entries[2].search_terms[..2]);
+    }
+}
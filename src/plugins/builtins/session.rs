@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::model::{EntryPlugin, ListEntry, RunFlags};
+
+/// The `[session]` table of the config, holding the command for each entry.
+/// An empty command hides that entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionConfig {
+    pub lock: Vec<String>,
+    pub logout: Vec<String>,
+    pub suspend: Vec<String>,
+    pub hibernate: Vec<String>,
+    pub reboot: Vec<String>,
+    pub shutdown: Vec<String>,
+}
+
+fn command(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| (*arg).to_owned()).collect()
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            lock: command(&["loginctl", "lock-session"]),
+            logout: command(&["sh", "-c", "loginctl terminate-session \"$XDG_SESSION_ID\""]),
+            suspend: command(&["systemctl", "suspend"]),
+            hibernate: command(&["systemctl", "hibernate"]),
+            reboot: command(&["systemctl", "reboot"]),
+            shutdown: command(&["systemctl", "poweroff"]),
+        }
+    }
+}
+
+pub struct SessionPlugin {
+    inner: Box<dyn Iterator<Item = ListEntry>>,
+}
+
+impl SessionPlugin {
+    pub fn new() -> Self {
+        Self {
+            inner: Box::new(None.into_iter()),
+        }
+    }
+}
+
+impl EntryPlugin for SessionPlugin {
+    fn name(&self) -> String {
+        "Session".to_owned()
+    }
+    fn start(&mut self, config: &Config) {
+        self.inner = Box::new(make_entries(&config.session).into_iter());
+    }
+    fn next(&mut self) -> Option<ListEntry> {
+        self.inner.next()
+    }
+}
+
+fn make_entries(config: &SessionConfig) -> Vec<ListEntry> {
+    // Anything that ends the session or loses unsaved work asks first.
+    let entries = [
+        ("Lock screen", &["lock"][..], &config.lock, false),
+        ("Log out", &["logout", "sign out"][..], &config.logout, true),
+        ("Suspend", &["sleep"][..], &config.suspend, false),
+        ("Hibernate", &[][..], &config.hibernate, false),
+        ("Reboot", &["restart"][..], &config.reboot, true),
+        (
+            "Shut down",
+            &["shutdown", "power off", "poweroff"][..],
+            &config.shutdown,
+            true,
+        ),
+    ];
+    entries
+        .iter()
+        .filter(|(_, _, command, _)| !command.is_empty())
+        .map(|(name, terms, command, confirm)| ListEntry {
+            display_name: Some((*name).to_owned()),
+            search_terms: terms.iter().map(|term| (*term).to_owned()).collect(),
+            exec_command: (*command).clone(),
+            exec_flags: RunFlags::new().with_confirm(*confirm),
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_entries() {
+        let config: SessionConfig = toml::from_str(
+            r#"
+lock = ["swaylock", "-f"]
+hibernate = []
+"#,
+        )
+        .unwrap();
+        let entries = make_entries(&config);
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|ent| {
+                (
+                    ent.name(),
+                    ent.exec_command.join(" "),
+                    ent.exec_flags.needs_confirm(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("Lock screen", "swaylock -f".to_owned(), false),
+                (
+                    "Log out",
+                    "sh -c loginctl terminate-session \"$XDG_SESSION_ID\"".to_owned(),
+                    true
+                ),
+                ("Suspend", "systemctl suspend".to_owned(), false),
+                ("Reboot", "systemctl reboot".to_owned(), true),
+                ("Shut down", "systemctl poweroff".to_owned(), true),
+            ],
+            summary
+        );
+    }
+}
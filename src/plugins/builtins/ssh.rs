@@ -0,0 +1,422 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::model::{EntryPlugin, ListEntry, RunFlags};
+use crate::utils::{expand_tilde, home_dir};
+
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+pub struct SshPlugin {
+    inner: Box<dyn Iterator<Item = ListEntry>>,
+}
+
+impl SshPlugin {
+    pub fn new() -> Self {
+        Self {
+            inner: Box::new(None.into_iter()),
+        }
+    }
+}
+
+impl EntryPlugin for SshPlugin {
+    fn name(&self) -> String {
+        "SSH Hosts".to_owned()
+    }
+    fn start(&mut self, _config: &Config) {
+        let ssh_dir = match home_dir() {
+            Some(home) => home.join(".ssh"),
+            None => {
+                return;
+            }
+        };
+        let mut parser = ConfigParser::new(ssh_dir.clone());
+        parser.parse_file(&ssh_dir.join("config"), 0);
+        let mut hosts = parser.hosts;
+        let mut candidates = configured_names(&hosts);
+        if let Ok(raw) = fs::read_to_string("/etc/hosts") {
+            candidates.extend(etc_hosts_names(&raw));
+        }
+        for known_hosts in &["known_hosts", "known_hosts2"] {
+            if let Ok(raw) = fs::read_to_string(ssh_dir.join(known_hosts)) {
+                merge_known_hosts(&mut hosts, &raw, &candidates);
+            }
+        }
+        self.inner = Box::new(hosts.into_iter().map(Host::into_entry));
+    }
+    fn next(&mut self) -> Option<ListEntry> {
+        self.inner.next()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Host {
+    alias: String,
+    hostname: Option<String>,
+    user: Option<String>,
+    port: Option<String>,
+    /// Whether the alias comes from `~/.ssh/config`, in which case ssh already
+    /// knows the rest of the options.
+    configured: bool,
+}
+
+impl Host {
+    fn into_entry(self) -> ListEntry {
+        let mut exec_command = vec!["ssh".to_owned()];
+        if let (Some(port), false) = (self.port.as_ref(), self.configured) {
+            exec_command.push("-p".to_owned());
+            exec_command.push(port.clone());
+        }
+        exec_command.push(self.alias.clone());
+
+        let mut search_terms = Vec::new();
+        search_terms.extend(self.hostname.clone());
+        search_terms.extend(self.user.clone());
+        if let Some(user) = self.user.as_ref() {
+            let hostname = self.hostname.as_ref().unwrap_or(&self.alias);
+            search_terms.push(format!("{}@{}", user, hostname));
+        }
+        ListEntry {
+            display_name: Some(format!("ssh {}", self.alias)),
+            search_terms,
+            exec_command,
+            exec_flags: RunFlags::new().with_term(true),
+            children: Vec::new(),
+            source: None,
+            tag: None,
+        }
+    }
+}
+
+/// Patterns containing wildcards or negations match many hosts, so they are
+/// not something we can connect to directly.
+fn is_concrete(pattern: &str) -> bool {
+    !pattern.contains(['*', '?', '!'])
+}
+
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+struct ConfigParser {
+    ssh_dir: PathBuf,
+    hosts: Vec<Host>,
+    /// Indices into `hosts` that the current `Host` block applies to.
+    current: Vec<usize>,
+}
+
+impl ConfigParser {
+    fn new(ssh_dir: PathBuf) -> Self {
+        Self {
+            ssh_dir,
+            hosts: Vec::new(),
+            current: Vec::new(),
+        }
+    }
+
+    fn parse_file(&mut self, path: &Path, depth: usize) {
+        match fs::read_to_string(path) {
+            Ok(raw) => self.parse(&raw, depth),
+            Err(e) => {
+                if path.exists() {
+                    eprintln!("ERROR: Could not read {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+
+    fn parse(&mut self, raw: &str, depth: usize) {
+        for line in raw.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (keyword, args) = split_keyword(line);
+            match keyword.to_lowercase().as_str() {
+                "host" => {
+                    self.current = args
+                        .iter()
+                        .filter(|pattern| is_concrete(pattern))
+                        .map(|alias| self.host_index(alias))
+                        .collect();
+                }
+                "match" => {
+                    self.current.clear();
+                }
+                "hostname" => self.set_option(args.first(), |host| &mut host.hostname),
+                "user" => self.set_option(args.first(), |host| &mut host.user),
+                "port" => self.set_option(args.first(), |host| &mut host.port),
+                "include" if depth < MAX_INCLUDE_DEPTH => {
+                    for pattern in args {
+                        self.include(&pattern, depth + 1);
+                    }
+                }
+                "include" => {
+                    eprintln!("ERROR: ssh config includes nested too deeply.");
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn host_index(&mut self, alias: &str) -> usize {
+        match self.hosts.iter().position(|host| host.alias == alias) {
+            Some(idx) => idx,
+            None => {
+                self.hosts.push(Host {
+                    alias: alias.to_owned(),
+                    configured: true,
+                    ..Host::default()
+                });
+                self.hosts.len() - 1
+            }
+        }
+    }
+
+    /// Like ssh itself, the first value given for an option wins.
+    fn set_option(
+        &mut self,
+        value: Option<&String>,
+        field: impl Fn(&mut Host) -> &mut Option<String>,
+    ) {
+        let value = match value {
+            Some(value) => value,
+            None => {
+                return;
+            }
+        };
+        for idx in self.current.iter() {
+            let host = &mut self.hosts[*idx];
+            let expanded = value.replace("%h", &host.alias).replace("%%", "%");
+            field(host).get_or_insert(expanded);
+        }
+    }
+
+    /// Relative include paths are resolved against `~/.ssh`.
+    fn include(&mut self, pattern: &str, depth: usize) {
+        let mut path = expand_tilde(pattern);
+        if path.is_relative() {
+            path = self.ssh_dir.join(path);
+        }
+        let paths = match glob::glob(&path.to_string_lossy()) {
+            Ok(paths) => paths,
+            Err(e) => {
+                eprintln!("ERROR: Bad ssh include pattern {:?}: {}", pattern, e);
+                return;
+            }
+        };
+        // Included files apply to the block they were included from.
+        let current = self.current.clone();
+        for path in paths.filter_map(|res| res.ok()) {
+            self.parse_file(&path, depth);
+            self.current = current.clone();
+        }
+    }
+}
+
+/// Splits a config line into its keyword and arguments. Keywords may be
+/// separated from their arguments by whitespace or `=`, and arguments may be
+/// double-quoted.
+fn split_keyword(line: &str) -> (&str, Vec<String>) {
+    let end = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let (keyword, rest) = line.split_at(end);
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest).trim_start();
+
+    let mut args = Vec::new();
+    let mut cur: Option<String> = None;
+    let mut in_quotes = false;
+    for c in rest.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                cur.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                args.extend(cur.take());
+            }
+            c => cur.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(cur);
+    (keyword, args)
+}
+
+/// The names and addresses of the hosts from `~/.ssh/config`. Hosts on
+/// another port are recorded as `[name]:port`, so those are added too.
+fn configured_names(hosts: &[Host]) -> Vec<String> {
+    let mut retvl = Vec::new();
+    for host in hosts {
+        let names = Some(&host.alias).into_iter().chain(host.hostname.as_ref());
+        for name in names {
+            retvl.push(name.clone());
+            if let Some(port) = host.port.as_ref() {
+                retvl.push(format!("[{}]:{}", name, port));
+            }
+        }
+    }
+    retvl
+}
+
+/// The host names in `/etc/hosts`, leaving out the addresses.
+fn etc_hosts_names(raw: &str) -> Vec<String> {
+    raw.lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .flat_map(|line| line.split_whitespace().skip(1))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Adds every host name in a `known_hosts` file. Hashed entries (those
+/// starting with `|1|`) cannot be turned back into names, so they are checked
+/// against `candidates` instead: a match is treated like the plain name.
+fn merge_known_hosts(hosts: &mut Vec<Host>, raw: &str, candidates: &[String]) {
+    for line in raw.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("@revoked") {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let mut names = fields.next();
+        if names.is_some_and(|marker| marker.starts_with('@')) {
+            names = fields.next();
+        }
+        let names = match names {
+            Some(names) => names,
+            None => {
+                continue;
+            }
+        };
+        for name in names.split(',') {
+            let name = match name.strip_prefix("|1|") {
+                Some(hashed) => match unhash(hashed, candidates) {
+                    Some(name) => name,
+                    None => {
+                        continue;
+                    }
+                },
+                None => name,
+            };
+            if !is_concrete(name) {
+                continue;
+            }
+            let (name, port) = match name
+                .strip_prefix('[')
+                .and_then(|rest| rest.split_once("]:"))
+            {
+                Some((name, port)) => (name, Some(port.to_owned())),
+                None => (name, None),
+            };
+            let already_known = hosts
+                .iter()
+                .any(|host| host.alias == name || host.hostname.as_deref() == Some(name));
+            if !already_known {
+                hosts.push(Host {
+                    alias: name.to_owned(),
+                    port,
+                    ..Host::default()
+                });
+            }
+        }
+    }
+}
+
+/// Finds the candidate that a hashed `known_hosts` name, given as
+/// `salt|hash` in base64, was made from.
+fn unhash<'a>(hashed: &str, candidates: &'a [String]) -> Option<&'a str> {
+    let (salt, hash) = hashed.split_once('|')?;
+    let salt = base64::decode(salt).ok()?;
+    let hash = base64::decode(hash).ok()?;
+    candidates
+        .iter()
+        .find(|name| {
+            Hmac::<Sha1>::new_from_slice(&salt).is_ok_and(|mut mac| {
+                mac.update(name.as_bytes());
+                mac.verify_slice(&hash).is_ok()
+            })
+        })
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    #[test]
+    fn parse_config() {
+        let ssh_dir = TempDir::new("ssh");
+        fs::create_dir_all(ssh_dir.join("config.d")).unwrap();
+        fs::write(
+            ssh_dir.join("config.d/work"),
+            "Host build\n    HostName build.%h.internal\n    User ci\n    Port 2222\n",
+        )
+        .unwrap();
+        let config = "\
+# Personal machines
+Host pi nas
+    User pi
+Host nas
+    HostName 192.168.1.20
+    User admin
+Host *.example.com !bastion
+    User nobody
+Match host foo
+    User ignored
+Host=\"quoted\"
+Include config.d/*
+";
+        let mut parser = ConfigParser::new(ssh_dir.to_path_buf());
+        parser.parse(config, 0);
+        let known = "\
+nas,192.168.1.20 ssh-ed25519 AAAA
+|1|abc=|def= ssh-ed25519 AAAA
+|1|AAECAwQFBgcICQoLDA0ODxAREhM=|sVB3965irwy1hHALRguiLiiHdXQ= ssh-ed25519 AAAA
+|1|AAECAwQFBgcICQoLDA0ODxAREhM=|XlZmwzGsjZ2zmcr13QlBjWuFTqo= ssh-ed25519 AAAA
+[git.example.org]:2222 ssh-rsa AAAA
+|1|AAECAwQFBgcICQoLDA0ODxAREhM=|kwODiGkIA1us7mp3c2/ye79wftw= ssh-rsa AAAA
+@cert-authority *.example.com ssh-rsa AAAA
+@revoked revoked.example.com ssh-rsa AAAA
+";
+        let mut candidates = configured_names(&parser.hosts);
+        candidates.extend(etc_hosts_names(
+            "127.0.0.1 localhost # loopback\n192.168.1.30 fileserver files\n",
+        ));
+        merge_known_hosts(&mut parser.hosts, known, &candidates);
+
+        let entries: Vec<_> = parser.hosts.into_iter().map(Host::into_entry).collect();
+        let commands: Vec<_> = entries
+            .iter()
+            .map(|ent| ent.exec_command.join(" "))
+            .collect();
+        assert_eq!(
+            vec![
+                "ssh pi",
+                "ssh nas",
+                "ssh quoted",
+                "ssh build",
+                "ssh fileserver",
+                "ssh -p 2222 git.example.org",
+            ],
+            commands
+        );
+        assert_eq!(
+            vec!["192.168.1.20", "pi", "pi@192.168.1.20"],
+            entries[1].search_terms
+        );
+        assert_eq!(
+            vec!["build.build.internal", "ci", "ci@build.build.internal"],
+            entries[3].search_terms
+        );
+        assert!(entries.iter().all(|ent| ent.exec_flags.is_term()));
+
+        assert_eq!(
+            Some("[build.build.internal]:2222"),
+            unhash(
+                "AAECAwQFBgcICQoLDA0ODxAREhM=|kwODiGkIA1us7mp3c2/ye79wftw=",
+                &candidates
+            )
+        );
+    }
+}
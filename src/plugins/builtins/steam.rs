@@ -0,0 +1,294 @@
+mod keyvalues;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::model::{EntryPlugin, ListEntry};
+use crate::plugins::builtins::mimeapps::MimeApps;
+use crate::utils::{format_size, home_dir};
+
+use keyvalues::KeyValue;
+
+/// Where Steam keeps its data for native, Flatpak and Snap installs, relative
+/// to the home directory. Several of these are usually symlinks to the same
+/// place.
+const STEAM_ROOTS: &[&str] = &[
+    ".steam/steam",
+    ".local/share/Steam",
+    ".var/app/com.valvesoftware.Steam/.local/share/Steam",
+    ".var/app/com.valvesoftware.Steam/.steam/steam",
+    "snap/steam/common/.local/share/Steam",
+];
+
+/// Steam installs compatibility tools and runtimes as apps too, but nobody
+/// wants to launch them.
+const TOOL_PREFIXES: &[&str] = &[
+    "Proton",
+    "Steam Linux Runtime",
+    "Steamworks Common Redistributables",
+    "Steamworks Shared",
+];
+
+pub struct SteamPlugin {
+    inner: Box<dyn Iterator<Item = ListEntry>>,
+}
+
+impl SteamPlugin {
+    pub fn new() -> Self {
+        Self {
+            inner: Box::new(None.into_iter()),
+        }
+    }
+}
+
+impl EntryPlugin for SteamPlugin {
+    fn name(&self) -> String {
+        "Steam".to_owned()
+    }
+    fn start(&mut self, _config: &Config) {
+        let home = match home_dir() {
+            Some(home) => home,
+            None => {
+                return;
+            }
+        };
+        let roots: Vec<PathBuf> = STEAM_ROOTS.iter().map(|root| home.join(root)).collect();
+        let games = find_games(&roots);
+        if games.is_empty() {
+            return;
+        }
+        let mime = MimeApps::load();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|dur| dur.as_secs())
+            .unwrap_or_default();
+        let entries: Vec<_> = games
+            .into_iter()
+            .map(|game| game.into_entry(&mime, now))
+            .collect();
+        self.inner = Box::new(entries.into_iter());
+    }
+    fn next(&mut self) -> Option<ListEntry> {
+        self.inner.next()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Game {
+    appid: String,
+    name: String,
+    /// Install size in bytes.
+    size: Option<u64>,
+    /// Unix timestamp, or 0 if never played.
+    last_played: u64,
+    manifest: PathBuf,
+}
+
+impl Game {
+    fn from_manifest(path: &Path) -> Option<Self> {
+        let raw = match fs::read_to_string(path) {
+            Ok(raw) => raw,
+            Err(e) => {
+                eprintln!("ERROR: Could not read {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        let doc = match keyvalues::parse(&raw) {
+            Some(doc) => doc,
+            None => {
+                eprintln!("ERROR: Could not parse {}", path.display());
+                return None;
+            }
+        };
+        let state = doc.get("AppState")?;
+        Some(Self {
+            appid: state.get_str("appid")?.to_owned(),
+            name: state.get_str("name")?.to_owned(),
+            size: state.get_str("SizeOnDisk").and_then(|raw| raw.parse().ok()),
+            last_played: state
+                .get_str("LastPlayed")
+                .and_then(|raw| raw.parse().ok())
+                .unwrap_or_default(),
+            manifest: path.to_owned(),
+        })
+    }
+
+    fn is_tool(&self) -> bool {
+        TOOL_PREFIXES
+            .iter()
+            .any(|prefix| self.name.starts_with(prefix))
+    }
+
+    fn into_entry(self, mime: &MimeApps, now: u64) -> ListEntry {
+        let url = format!("steam://rungameid/{}", self.appid);
+        let (exec_command, exec_flags) = mime.open_url(&url);
+        let mut search_terms = vec!["Steam".to_owned(), "Game".to_owned()];
+        search_terms.extend(self.size.map(format_size));
+        search_terms.push(describe_last_played(self.last_played, now));
+        ListEntry {
+            display_name: Some(self.name),
+            search_terms,
+            exec_command,
+            exec_flags,
+            children: Vec::new(),
+            source: Some(self.manifest),
+            tag: None,
+        }
+    }
+}
+
+fn describe_last_played(last_played: u64, now: u64) -> String {
+    if last_played == 0 {
+        return "Never played".to_owned();
+    }
+    match now.saturating_sub(last_played) / (24 * 60 * 60) {
+        0 => "Last played today".to_owned(),
+        1 => "Last played yesterday".to_owned(),
+        days => format!("Last played {} days ago", days),
+    }
+}
+
+/// Every library folder known to a Steam root, including the root itself.
+/// Older clients list the paths directly under numbered keys; newer ones use
+/// a block with a `path` field.
+fn library_folders(root: &Path) -> Vec<PathBuf> {
+    let mut retvl = vec![root.to_owned()];
+    let vdf = root.join("steamapps/libraryfolders.vdf");
+    let raw = match fs::read_to_string(&vdf) {
+        Ok(raw) => raw,
+        Err(_) => {
+            return retvl;
+        }
+    };
+    let doc = match keyvalues::parse(&raw) {
+        Some(doc) => doc,
+        None => {
+            eprintln!("ERROR: Could not parse {}", vdf.display());
+            return retvl;
+        }
+    };
+    let folders = doc.get("libraryfolders").map(KeyValue::fields);
+    for (key, value) in folders.unwrap_or_default() {
+        let path = match value {
+            KeyValue::Str(path) if key.parse::<u32>().is_ok() => path.as_str(),
+            KeyValue::Block(_) => match value.get_str("path") {
+                Some(path) => path,
+                None => continue,
+            },
+            KeyValue::Str(_) => continue,
+        };
+        retvl.push(PathBuf::from(path));
+    }
+    retvl
+}
+
+/// Reads every installed game from the given Steam roots, most recently
+/// played first.
+fn find_games(roots: &[PathBuf]) -> Vec<Game> {
+    let mut seen_libraries = HashSet::new();
+    let mut seen_apps = HashSet::new();
+    let mut retvl = Vec::new();
+    let existing_roots = roots.iter().filter_map(|root| root.canonicalize().ok());
+    for library in existing_roots.flat_map(|root| library_folders(&root)) {
+        let library = match library.canonicalize() {
+            Ok(library) => library,
+            Err(_) => continue,
+        };
+        if !seen_libraries.insert(library.clone()) {
+            continue;
+        }
+        let steamapps = match fs::read_dir(library.join("steamapps")) {
+            Ok(steamapps) => steamapps,
+            Err(_) => continue,
+        };
+        for ent in steamapps.filter_map(|ent| ent.ok()) {
+            let file_name = ent.file_name().to_string_lossy().into_owned();
+            if !file_name.starts_with("appmanifest_") || !file_name.ends_with(".acf") {
+                continue;
+            }
+            let game = match Game::from_manifest(&ent.path()) {
+                Some(game) => game,
+                None => continue,
+            };
+            if !game.is_tool() && seen_apps.insert(game.appid.clone()) {
+                retvl.push(game);
+            }
+        }
+    }
+    retvl.sort_by(|a, b| {
+        b.last_played
+            .cmp(&a.last_played)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    retvl
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    fn manifest(appid: &str, name: &str, size: &str, last_played: &str) -> String {
+        format!(
+            "\"AppState\"\n{{\n\t\"appid\"\t\t\"{}\"\n\t\"name\"\t\t\"{}\"\n\t\"SizeOnDisk\"\t\t\"{}\"\n\t\"LastPlayed\"\t\t\"{}\"\n}}\n",
+            appid, name, size, last_played
+        )
+    }
+
+    #[test]
+    fn read_libraries() {
+        let base = TempDir::new("steam");
+        let root = base.join("Steam");
+        let extra = base.join("Games");
+        fs::create_dir_all(root.join("steamapps")).unwrap();
+        fs::create_dir_all(extra.join("steamapps")).unwrap();
+        fs::write(
+            root.join("steamapps/libraryfolders.vdf"),
+            format!(
+                "\"libraryfolders\"\n{{\n\t\"0\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n\t\"1\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n}}\n",
+                root.display(),
+                extra.display()
+            ),
+        )
+        .unwrap();
+        let manifests = [
+            (&root, "570", "Dota 2", "32212254720", "1600000000"),
+            (&root, "1493710", "Proton Experimental", "1073741824", "0"),
+            (&extra, "620", "Portal 2", "12884901888", "1600090000"),
+            (&extra, "400", "Portal", "4294967296", "0"),
+            (&extra, "1391110", "Steam Linux Runtime - soldier", "1", "0"),
+        ];
+        for (library, appid, name, size, last_played) in manifests.iter() {
+            fs::write(
+                library.join(format!("steamapps/appmanifest_{}.acf", appid)),
+                manifest(appid, name, size, last_played),
+            )
+            .unwrap();
+        }
+        // The same root reached through a symlink is only read once.
+        std::os::unix::fs::symlink(&root, base.join("link")).unwrap();
+        let games = find_games(&[root.clone(), base.join("link"), base.join("missing")]);
+
+        let names: Vec<_> = games.iter().map(|game| game.name.as_str()).collect();
+        assert_eq!(vec!["Portal 2", "Dota 2", "Portal"], names);
+
+        let now = 1600090000 + 3 * 24 * 60 * 60;
+        let entry = games[1].clone().into_entry(&MimeApps::default(), now);
+        assert_eq!(
+            vec!["xdg-open", "steam://rungameid/570"],
+            entry.exec_command
+        );
+        assert_eq!(
+            vec!["Steam", "Game", "30.0 GiB", "Last played 4 days ago"],
+            entry.search_terms
+        );
+        let entry = games[2].clone().into_entry(&MimeApps::default(), now);
+        assert_eq!(
+            vec!["Steam", "Game", "4.0 GiB", "Never played"],
+            entry.search_terms
+        );
+    }
+}
@@ -0,0 +1,162 @@
+//! A reader for Valve's text KeyValues format, as used by `libraryfolders.vdf`
+//! and `appmanifest_*.acf`.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyValue {
+    Str(String),
+    Block(Vec<(String, KeyValue)>),
+}
+
+impl KeyValue {
+    /// Looks up a key in a block. Keys are case-insensitive; Steam itself is
+    /// not consistent about their case.
+    pub fn get(&self, key: &str) -> Option<&KeyValue> {
+        match self {
+            KeyValue::Block(fields) => fields
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, v)| v),
+            KeyValue::Str(_) => None,
+        }
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            KeyValue::Str(value) => Some(value),
+            KeyValue::Block(_) => None,
+        }
+    }
+
+    pub fn fields(&self) -> &[(String, KeyValue)] {
+        match self {
+            KeyValue::Block(fields) => fields,
+            KeyValue::Str(_) => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Str(String),
+    Open,
+    Close,
+}
+
+struct Tokens<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Iterator for Tokens<'_> {
+    type Item = Token;
+    fn next(&mut self) -> Option<Token> {
+        loop {
+            match self.chars.next()? {
+                c if c.is_whitespace() => {}
+                '{' => return Some(Token::Open),
+                '}' => return Some(Token::Close),
+                '/' if self.chars.peek() == Some(&'/') => {
+                    self.chars.find(|c| *c == '\n');
+                }
+                // Platform conditionals like `[$WIN32]` follow a value; we do
+                // not evaluate them.
+                '[' => {
+                    self.chars.find(|c| *c == ']');
+                }
+                '"' => {
+                    let mut retvl = String::new();
+                    while let Some(c) = self.chars.next() {
+                        match c {
+                            '"' => break,
+                            '\\' => match self.chars.next() {
+                                Some('n') => retvl.push('\n'),
+                                Some('t') => retvl.push('\t'),
+                                Some(other) => retvl.push(other),
+                                None => break,
+                            },
+                            c => retvl.push(c),
+                        }
+                    }
+                    return Some(Token::Str(retvl));
+                }
+                c => {
+                    let mut retvl = c.to_string();
+                    while let Some(c) = self.chars.peek() {
+                        if c.is_whitespace() || matches!(c, '"' | '{' | '}') {
+                            break;
+                        }
+                        retvl.push(*c);
+                        self.chars.next();
+                    }
+                    return Some(Token::Str(retvl));
+                }
+            }
+        }
+    }
+}
+
+/// Parses a document into a block holding its top level keys, or `None` if
+/// it is malformed.
+pub fn parse(raw: &str) -> Option<KeyValue> {
+    let mut tokens = Tokens {
+        chars: raw.chars().peekable(),
+    };
+    let fields = parse_block(&mut tokens, true)?;
+    Some(KeyValue::Block(fields))
+}
+
+fn parse_block(tokens: &mut Tokens<'_>, top_level: bool) -> Option<Vec<(String, KeyValue)>> {
+    let mut fields = Vec::new();
+    loop {
+        let key = match tokens.next() {
+            Some(Token::Str(key)) => key,
+            Some(Token::Close) if !top_level => return Some(fields),
+            None if top_level => return Some(fields),
+            _ => return None,
+        };
+        let value = match tokens.next()? {
+            Token::Str(value) => KeyValue::Str(value),
+            Token::Open => KeyValue::Block(parse_block(tokens, false)?),
+            Token::Close => return None,
+        };
+        fields.push((key, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_document() {
+        let raw = r#"
+// Written by Steam
+"libraryfolders"
+{
+	"0"
+	{
+		"path"		"/home/me/.local/share/Steam"
+		"label"		"Quoted \"label\" \\ here"
+		"apps"
+		{
+			"570"		"31290447189"
+		}
+	}
+	Unquoted	value [$LINUX]
+}
+"#;
+        let doc = parse(raw).unwrap();
+        let folders = doc.get("LibraryFolders").unwrap();
+        let first = folders.get("0").unwrap();
+        assert_eq!(Some("/home/me/.local/share/Steam"), first.get_str("path"));
+        assert_eq!(Some("Quoted \"label\" \\ here"), first.get_str("label"));
+        assert_eq!(
+            Some("31290447189"),
+            first.get("apps").and_then(|apps| apps.get_str("570"))
+        );
+        assert_eq!(Some("value"), folders.get_str("unquoted"));
+        assert_eq!(None, parse("\"unclosed\" {"));
+    }
+}
@@ -0,0 +1,388 @@
+use crate::config::Config;
+use crate::model::{EntryPlugin, ListEntry, RunFlags};
+
+pub struct UnitsPlugin {}
+
+impl UnitsPlugin {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl EntryPlugin for UnitsPlugin {
+    fn name(&self) -> String {
+        "Unit Conversion".to_owned()
+    }
+    fn start(&mut self, _config: &Config) {}
+    fn next(&mut self) -> Option<ListEntry> {
+        None
+    }
+    fn query(&mut self, text: &str) -> Vec<ListEntry> {
+        let conversion = match Conversion::parse(text) {
+            Some(conv) => conv,
+            None => {
+                return Vec::new();
+            }
+        };
+        let value = format_value(conversion.value);
+        vec![ListEntry {
+            display_name: Some(format!("= {} {}", value, conversion.target)),
+            search_terms: vec![text.to_owned()],
+            exec_command: vec!["wl-copy".to_owned(), "--".to_owned(), value],
+            exec_flags: RunFlags::new(),
+            children: Vec::new(),
+            source: None,
+            tag: None,
+        }]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Length,
+    Mass,
+    Temperature,
+    Data,
+    Time,
+    Speed,
+}
+
+/// A unit is converted into its dimension's base unit as `value * factor + offset`.
+struct Unit {
+    names: &'static [&'static str],
+    dimension: Dimension,
+    factor: f64,
+    offset: f64,
+}
+
+const fn unit(names: &'static [&'static str], dimension: Dimension, factor: f64) -> Unit {
+    Unit {
+        names,
+        dimension,
+        factor,
+        offset: 0.0,
+    }
+}
+
+const KIB: f64 = 1024.0;
+
+/// Base units are metres, kilograms, kelvin, bytes, seconds and metres per second.
+const UNITS: &[Unit] = &[
+    // Length
+    unit(
+        &["m", "meter", "meters", "metre", "metres"],
+        Dimension::Length,
+        1.0,
+    ),
+    unit(
+        &["km", "kilometer", "kilometers", "kilometre", "kilometres"],
+        Dimension::Length,
+        1e3,
+    ),
+    unit(
+        &[
+            "cm",
+            "centimeter",
+            "centimeters",
+            "centimetre",
+            "centimetres",
+        ],
+        Dimension::Length,
+        1e-2,
+    ),
+    unit(
+        &[
+            "mm",
+            "millimeter",
+            "millimeters",
+            "millimetre",
+            "millimetres",
+        ],
+        Dimension::Length,
+        1e-3,
+    ),
+    unit(
+        &["um", "µm", "micrometer", "micrometers", "micron", "microns"],
+        Dimension::Length,
+        1e-6,
+    ),
+    unit(&["nm", "nanometer", "nanometers"], Dimension::Length, 1e-9),
+    unit(&["mi", "mile", "miles"], Dimension::Length, 1609.344),
+    unit(&["yd", "yard", "yards"], Dimension::Length, 0.9144),
+    unit(&["ft", "foot", "feet", "'"], Dimension::Length, 0.3048),
+    unit(&["in", "inch", "inches", "\""], Dimension::Length, 0.0254),
+    unit(
+        &["nmi", "nautical mile", "nautical miles"],
+        Dimension::Length,
+        1852.0,
+    ),
+    // Mass
+    unit(
+        &["kg", "kilogram", "kilograms", "kilo", "kilos"],
+        Dimension::Mass,
+        1.0,
+    ),
+    unit(&["g", "gram", "grams"], Dimension::Mass, 1e-3),
+    unit(&["mg", "milligram", "milligrams"], Dimension::Mass, 1e-6),
+    unit(
+        &["ug", "µg", "microgram", "micrograms"],
+        Dimension::Mass,
+        1e-9,
+    ),
+    unit(
+        &["t", "tonne", "tonnes", "metric ton", "metric tons"],
+        Dimension::Mass,
+        1e3,
+    ),
+    unit(
+        &["lb", "lbs", "pound", "pounds"],
+        Dimension::Mass,
+        0.453_592_37,
+    ),
+    unit(
+        &["oz", "ounce", "ounces"],
+        Dimension::Mass,
+        0.028_349_523_125,
+    ),
+    unit(&["st", "stone", "stones"], Dimension::Mass, 6.350_293_18),
+    // Temperature
+    Unit {
+        names: &["C", "°C", "celsius", "degC"],
+        dimension: Dimension::Temperature,
+        factor: 1.0,
+        offset: 273.15,
+    },
+    Unit {
+        names: &["F", "°F", "fahrenheit", "degF"],
+        dimension: Dimension::Temperature,
+        factor: 5.0 / 9.0,
+        offset: 273.15 - 32.0 * 5.0 / 9.0,
+    },
+    unit(&["K", "kelvin"], Dimension::Temperature, 1.0),
+    unit(&["R", "°R", "rankine"], Dimension::Temperature, 5.0 / 9.0),
+    // Data
+    unit(&["B", "byte", "bytes"], Dimension::Data, 1.0),
+    unit(&["b", "bit", "bits"], Dimension::Data, 0.125),
+    unit(&["kB", "KB", "kilobyte", "kilobytes"], Dimension::Data, 1e3),
+    unit(&["MB", "megabyte", "megabytes"], Dimension::Data, 1e6),
+    unit(&["GB", "gigabyte", "gigabytes"], Dimension::Data, 1e9),
+    unit(&["TB", "terabyte", "terabytes"], Dimension::Data, 1e12),
+    unit(&["PB", "petabyte", "petabytes"], Dimension::Data, 1e15),
+    unit(&["KiB", "kibibyte", "kibibytes"], Dimension::Data, KIB),
+    unit(
+        &["MiB", "mebibyte", "mebibytes"],
+        Dimension::Data,
+        KIB * KIB,
+    ),
+    unit(
+        &["GiB", "gibibyte", "gibibytes"],
+        Dimension::Data,
+        KIB * KIB * KIB,
+    ),
+    unit(
+        &["TiB", "tebibyte", "tebibytes"],
+        Dimension::Data,
+        KIB * KIB * KIB * KIB,
+    ),
+    unit(
+        &["PiB", "pebibyte", "pebibytes"],
+        Dimension::Data,
+        KIB * KIB * KIB * KIB * KIB,
+    ),
+    unit(
+        &["kb", "Kb", "kbit", "kilobit", "kilobits"],
+        Dimension::Data,
+        1e3 / 8.0,
+    ),
+    unit(
+        &["Mb", "Mbit", "megabit", "megabits"],
+        Dimension::Data,
+        1e6 / 8.0,
+    ),
+    unit(
+        &["Gb", "Gbit", "gigabit", "gigabits"],
+        Dimension::Data,
+        1e9 / 8.0,
+    ),
+    // Time
+    unit(
+        &["s", "sec", "secs", "second", "seconds"],
+        Dimension::Time,
+        1.0,
+    ),
+    unit(
+        &["ms", "millisecond", "milliseconds"],
+        Dimension::Time,
+        1e-3,
+    ),
+    unit(
+        &["us", "µs", "microsecond", "microseconds"],
+        Dimension::Time,
+        1e-6,
+    ),
+    unit(&["ns", "nanosecond", "nanoseconds"], Dimension::Time, 1e-9),
+    unit(
+        &["m", "min", "mins", "minute", "minutes"],
+        Dimension::Time,
+        60.0,
+    ),
+    unit(
+        &["h", "hr", "hrs", "hour", "hours"],
+        Dimension::Time,
+        3600.0,
+    ),
+    unit(&["d", "day", "days"], Dimension::Time, 86400.0),
+    unit(&["wk", "week", "weeks"], Dimension::Time, 604_800.0),
+    unit(
+        &["y", "yr", "yrs", "year", "years"],
+        Dimension::Time,
+        31_557_600.0,
+    ),
+    // Speed
+    unit(&["m/s", "mps"], Dimension::Speed, 1.0),
+    unit(&["km/h", "kmh", "kph", "kmph"], Dimension::Speed, 1.0 / 3.6),
+    unit(&["mph", "mi/h"], Dimension::Speed, 0.447_04),
+    unit(&["ft/s", "fps"], Dimension::Speed, 0.3048),
+    unit(&["kn", "kt", "knot", "knots"], Dimension::Speed, 0.514_444),
+];
+
+/// All units a name could refer to, preferring exact (case-sensitive)
+/// matches since eg `B` and `b` mean different things.
+fn lookup(name: &str) -> Vec<&'static Unit> {
+    let exact: Vec<_> = UNITS
+        .iter()
+        .filter(|unit| unit.names.contains(&name))
+        .collect();
+    if !exact.is_empty() {
+        return exact;
+    }
+    let lower = name.to_lowercase();
+    UNITS
+        .iter()
+        .filter(|unit| unit.names.iter().any(|n| n.to_lowercase() == lower))
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conversion {
+    pub value: f64,
+    pub target: String,
+}
+
+const SEPARATORS: &[&str] = &[" in ", " to ", " as ", " -> ", " => "];
+
+impl Conversion {
+    /// Parses queries of the form `<amount> <unit> in <unit>`, where the
+    /// amount can be a compound like `1h30m` or `5 ft 3 in`.
+    pub fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        let (split_idx, sep) = SEPARATORS
+            .iter()
+            .filter_map(|sep| raw.rfind(sep).map(|idx| (idx, *sep)))
+            .max_by_key(|(idx, _)| *idx)?;
+        let quantity = parse_quantity(&raw[..split_idx])?;
+        let target_name = raw[split_idx + sep.len()..].trim();
+        let targets = lookup(target_name);
+
+        // Pick the dimension that every unit in the query agrees on, which
+        // is what lets `m` mean minutes in `1h30m` but metres in `5 m in ft`.
+        let dimension = targets.iter().map(|unit| unit.dimension).find(|dim| {
+            quantity
+                .iter()
+                .all(|(_, units)| units.iter().any(|unit| unit.dimension == *dim))
+        })?;
+        let target = targets.iter().find(|unit| unit.dimension == dimension)?;
+        if dimension == Dimension::Temperature && quantity.len() != 1 {
+            return None;
+        }
+        let base: f64 = quantity
+            .iter()
+            .map(|(amount, units)| {
+                let unit = units
+                    .iter()
+                    .find(|unit| unit.dimension == dimension)
+                    .unwrap();
+                amount * unit.factor + unit.offset
+            })
+            .sum();
+        let value = (base - target.offset) / target.factor;
+        Some(Conversion {
+            value,
+            target: target_name.to_owned(),
+        })
+    }
+}
+
+fn parse_quantity(raw: &str) -> Option<Vec<(f64, Vec<&'static Unit>)>> {
+    let mut retvl = Vec::new();
+    let mut rest = raw.trim();
+    while !rest.is_empty() {
+        let num_len = rest
+            .char_indices()
+            .find(|(idx, c)| {
+                !(c.is_ascii_digit() || *c == '.' || (*idx == 0 && (*c == '-' || *c == '+')))
+            })
+            .map_or(rest.len(), |(idx, _)| idx);
+        let amount: f64 = rest[..num_len].parse().ok()?;
+        rest = rest[num_len..].trim_start();
+        let unit_len = rest
+            .char_indices()
+            .find(|(_, c)| c.is_whitespace() || c.is_ascii_digit() || *c == '-' || *c == '+')
+            .map_or(rest.len(), |(idx, _)| idx);
+        let units = lookup(&rest[..unit_len]);
+        if units.is_empty() {
+            return None;
+        }
+        retvl.push((amount, units));
+        rest = rest[unit_len..].trim_start();
+    }
+    if retvl.is_empty() {
+        None
+    } else {
+        Some(retvl)
+    }
+}
+
+fn format_value(value: f64) -> String {
+    if value != 0.0 && !(1e-4..1e15).contains(&value.abs()) {
+        return format!("{:e}", value);
+    }
+    let raw = format!("{:.6}", value);
+    let trimmed = raw.trim_end_matches('0').trim_end_matches('.');
+    if trimmed == "-0" {
+        "0".to_owned()
+    } else {
+        trimmed.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(raw: &str) -> String {
+        format_value(Conversion::parse(raw).unwrap().value)
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!("8.04672", convert("5 mi in km"));
+        assert_eq!("22.222222", convert("72 F to C"));
+        assert_eq!("-40", convert("-40 C to F"));
+        assert_eq!("3221.225472", convert("3 GiB in MB"));
+        assert_eq!("5400", convert("1h30m in s"));
+        assert_eq!("16.404199", convert("5 m in ft"));
+        assert_eq!("1.5", convert("90 m in h"));
+        assert_eq!("160.02", convert("5 ft 3 in in cm"));
+        assert_eq!("100", convert("100 km/h in kph"));
+        assert_eq!("1", convert("8 bits in bytes"));
+    }
+
+    #[test]
+    fn rejects() {
+        assert_eq!(None, Conversion::parse("firefox"));
+        assert_eq!(None, Conversion::parse("5 mi in kg"));
+        assert_eq!(None, Conversion::parse("5 in"));
+        assert_eq!(None, Conversion::parse("5 parsecs in km"));
+    }
+}
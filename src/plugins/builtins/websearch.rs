@@ -0,0 +1,142 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::model::{EntryPlugin, ListEntry};
+use crate::plugins::builtins::mimeapps::MimeApps;
+
+/// Everything but the characters RFC 3986 leaves unreserved.
+const QUERY_ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// A `[[search_engine]]` block of the config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchEngine {
+    /// The word typed before the query, like `g` in `g rust lifetimes`.
+    pub keyword: String,
+    pub name: String,
+    /// The URL to open, with `{query}` replaced by the encoded query.
+    pub url: String,
+    /// Offer this engine for any text that matches nothing else. Only the
+    /// first fallback engine is used.
+    #[serde(default)]
+    pub fallback: bool,
+}
+
+impl SearchEngine {
+    fn url_for(&self, query: &str) -> String {
+        let encoded = utf8_percent_encode(query, QUERY_ENCODE).to_string();
+        self.url.replace("{query}", &encoded)
+    }
+
+    fn make_entry(&self, text: &str, query: &str, mime: &MimeApps) -> ListEntry {
+        let (exec_command, exec_flags) = mime.open_url(&self.url_for(query));
+        ListEntry {
+            display_name: Some(format!("Search {} for \"{}\"", self.name, query)),
+            search_terms: vec![text.to_owned()],
+            exec_command,
+            exec_flags,
+            ..Default::default()
+        }
+    }
+}
+
+pub struct WebSearchPlugin {
+    engines: Vec<SearchEngine>,
+    mime: MimeApps,
+}
+
+impl WebSearchPlugin {
+    pub fn new() -> Self {
+        Self {
+            engines: Vec::new(),
+            mime: MimeApps::default(),
+        }
+    }
+}
+
+impl EntryPlugin for WebSearchPlugin {
+    fn name(&self) -> String {
+        "Web Search".to_owned()
+    }
+    fn start(&mut self, config: &Config) {
+        self.engines = config.search_engines.clone();
+        self.mime = MimeApps::load();
+    }
+    fn next(&mut self) -> Option<ListEntry> {
+        None
+    }
+    fn query(&mut self, text: &str) -> Vec<ListEntry> {
+        let (keyword, query) = match text.trim_start().split_once(' ') {
+            Some((keyword, query)) if !query.trim().is_empty() => (keyword, query.trim()),
+            _ => {
+                return Vec::new();
+            }
+        };
+        self.engines
+            .iter()
+            .filter(|engine| engine.keyword == keyword)
+            .map(|engine| engine.make_entry(text, query, &self.mime))
+            .collect()
+    }
+    fn fallback(&mut self, text: &str) -> Vec<ListEntry> {
+        let query = text.trim();
+        self.engines
+            .iter()
+            .find(|engine| engine.fallback)
+            .map(|engine| engine.make_entry(text, query, &self.mime))
+            .into_iter()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyword_and_fallback() {
+        let config: Config = toml::from_str(
+            r#"
+[[search_engine]]
+keyword = "g"
+name = "Google"
+url = "https://www.google.com/search?q={query}"
+
+[[search_engine]]
+keyword = "d"
+name = "DuckDuckGo"
+url = "https://duckduckgo.com/?q={query}"
+fallback = true
+"#,
+        )
+        .unwrap();
+        let mut plugin = WebSearchPlugin::new();
+        plugin.engines = config.search_engines;
+
+        let found = plugin.query("g rust lifetimes & c++");
+        assert_eq!(1, found.len());
+        assert_eq!(
+            "Search Google for \"rust lifetimes & c++\"",
+            found[0].name()
+        );
+        assert_eq!(
+            vec![
+                "xdg-open",
+                "https://www.google.com/search?q=rust%20lifetimes%20%26%20c%2B%2B"
+            ],
+            found[0].exec_command
+        );
+        assert!(plugin.query("g ").is_empty());
+        assert!(plugin.query("gimp").is_empty());
+
+        let fallback = plugin.fallback("what is ünicode");
+        assert_eq!(
+            "https://duckduckgo.com/?q=what%20is%20%C3%BCnicode",
+            fallback[0].exec_command[1]
+        );
+    }
+}
@@ -2,15 +2,18 @@ mod dummy;
 use dummy::DummyPlugin;
 
 use crate::model::EntryPlugin;
+#[cfg(all(test, feature = "plugin-lua"))]
+use crate::utils::TempDir;
 
 use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
 
 use std::path::PathBuf;
 
 #[cfg(feature = "plugin-lua")]
 mod luaplugin;
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(tag = "kind", rename_all = "kebab-case")]
 pub enum LoadablePlugins {
     Dummy,
@@ -18,6 +21,16 @@ pub enum LoadablePlugins {
 }
 
 impl LoadablePlugins {
+    /// What to call the plugin before it has started and can name itself.
+    pub fn label(&self) -> String {
+        match self {
+            Self::Dummy => "dummy".to_owned(),
+            Self::Lua(conf) => match conf.name.as_ref() {
+                Some(name) => name.clone(),
+                None => conf.file.display().to_string(),
+            },
+        }
+    }
     pub fn load(&self) -> Box<dyn EntryPlugin> {
         match self {
             Self::Dummy => Box::new(DummyPlugin {}),
@@ -26,12 +39,67 @@ impl LoadablePlugins {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+/// Something a Lua plugin may do beyond building entries, granted through the
+/// `allow` list of its `[[plugin]]` block.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Read files in the home directory.
+    ReadHome,
+    /// Read any file.
+    ReadAll,
+    /// Create, write, rename and delete files in the home directory.
+    WriteHome,
+    /// Run other programs with `io.popen`, `os.execute` or `tmpas.exec`.
+    Exec,
+    /// Read environment variables.
+    Env,
+    /// Load other Lua modules with `require`.
+    Require,
+}
+
+impl Capability {
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::ReadHome => "read_home",
+            Capability::ReadAll => "read_all",
+            Capability::WriteHome => "write_home",
+            Capability::Exec => "exec",
+            Capability::Env => "env",
+            Capability::Require => "require",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct LuaConfig {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     #[serde(alias = "source")]
     pub file: PathBuf,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<Capability>,
+    /// How long a single call into the script may run before the plugin is
+    /// stopped.
+    #[serde(default = "default_time_limit_ms")]
+    pub time_limit_ms: u64,
+    /// How much memory the script may use before the plugin is stopped. It
+    /// is checked between instructions, so one large allocation can exceed
+    /// it.
+    #[serde(default = "default_memory_limit_mb")]
+    pub memory_limit_mb: usize,
+    /// Any other keys in the `[[plugin]]` block, handed to the script as its
+    /// `config` global.
+    #[serde(flatten, skip_serializing_if = "toml::value::Table::is_empty")]
+    pub settings: toml::value::Table,
+}
+
+fn default_time_limit_ms() -> u64 {
+    2000
+}
+
+fn default_memory_limit_mb() -> usize {
+    128
 }
 
 impl LuaConfig {
@@ -57,3 +125,77 @@ impl LuaConfig {
         Box::new(DummyPlugin {})
     }
 }
+
+/// Loads a Lua plugin on its own and prints every entry it produces, along
+/// with any errors and how long each step took.
+#[derive(Debug, StructOpt)]
+#[cfg_attr(not(feature = "plugin-lua"), allow(dead_code))]
+pub struct PluginTest {
+    /// The plugin file
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+    /// A TOML file with the rest of the plugin's `[[plugin]]` block, such as
+    /// its `allow` list and settings
+    #[structopt(long, parse(from_os_str))]
+    settings: Option<PathBuf>,
+    /// Also run the plugin's query callbacks with this text
+    #[structopt(long)]
+    query: Option<String>,
+    /// Stop after this many entries
+    #[structopt(long)]
+    limit: Option<usize>,
+    /// Give up on a plugin that is still producing entries after this long
+    #[structopt(long, default_value = "10000")]
+    timeout_ms: u64,
+    /// Print JSON instead of a tree
+    #[structopt(long)]
+    json: bool,
+}
+
+impl PluginTest {
+    /// Runs the test and returns the exit code.
+    #[cfg(feature = "plugin-lua")]
+    pub fn run(&self) -> i32 {
+        luaplugin::harness::run(self)
+    }
+    #[cfg(not(feature = "plugin-lua"))]
+    pub fn run(&self) -> i32 {
+        eprintln!(
+            "ERROR: Cannot test Lua plugin {:?}: Lua support has been disabled!",
+            self.file
+        );
+        1
+    }
+}
+
+/// Writes `source` to a plugin file in a directory of its own and reads
+/// `extra_toml` as the rest of its `[[plugin]]` block. The file lasts as long
+/// as the directory.
+#[cfg(all(test, feature = "plugin-lua"))]
+pub fn lua_config(source: &str, extra_toml: &str) -> (TempDir, LuaConfig) {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Tests run in parallel, so no two fixtures may share a directory.
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    let dir = TempDir::new(&format!("lua-{}", NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+    let file = dir.join("plugin.lua");
+    std::fs::write(&file, source).unwrap();
+    let raw = format!(
+        "kind = 'lua'\nfile = {:?}\n{}",
+        file.display().to_string(),
+        extra_toml
+    );
+    match toml::from_str(&raw).unwrap() {
+        LoadablePlugins::Lua(conf) => (dir, conf),
+        other => panic!("Expected a lua plugin, got {:?}", other),
+    }
+}
+
+/// The plugin `lua_config` describes, already started.
+#[cfg(all(test, feature = "plugin-lua"))]
+pub fn lua_plugin(source: &str, extra_toml: &str) -> luaplugin::LuaPlugin {
+    let (_dir, conf) = lua_config(source, extra_toml);
+    let mut plugin = luaplugin::LuaPlugin::new(conf).unwrap();
+    plugin.start(&crate::config::Config::default());
+    plugin
+}
@@ -1,33 +1,80 @@
 use super::LuaConfig;
 use crate::config::Config;
-use crate::model::{EntryPlugin, ListEntry, RunFlags};
+use crate::model::{next_tag, EntryPlugin, ListEntry, RunFlags, Selected};
 
 use anyhow::{Context, Error};
-use mlua::{self, FromLua, Lua, Value as LuaValue};
+use mlua::{self, FromLua, Lua, LuaSerdeExt, RegistryKey, ThreadStatus, Value as LuaValue};
 
 use std::cmp::{Eq, PartialEq};
+use std::collections::HashMap;
 use std::fs;
+use std::task::Poll;
+use std::time::Duration;
 
 mod api;
 use api::STATE_KEY;
 
+mod sandbox;
+use sandbox::Watchdog;
+
+pub mod harness;
+
 pub struct LuaPlugin {
     conf: LuaConfig,
     env: Lua,
+    watchdog: Watchdog,
+    /// Callbacks of the entries from `next` and `entries`.
+    loaded: Callbacks,
+    /// Callbacks of the entries from the latest query, and of anything the
+    /// user has drilled into since.
+    queried: Callbacks,
+    /// Callbacks of the query before that, since the launcher may still be
+    /// showing its results.
+    previous: Callbacks,
 }
 
 impl LuaPlugin {
     pub fn new(conf: LuaConfig) -> mlua::Result<Self> {
-        let env = Lua::new();
+        let env = sandbox::new_state();
         api::register(&env)?;
-        Ok(Self { conf, env })
+        let watchdog = Watchdog::install(
+            &env,
+            Duration::from_millis(conf.time_limit_ms),
+            conf.memory_limit_mb * 1024 * 1024,
+        )?;
+        sandbox::restrict(&env, &conf.allow)?;
+        Ok(Self {
+            conf,
+            env,
+            watchdog,
+            loaded: Callbacks::default(),
+            queried: Callbacks::default(),
+            previous: Callbacks::default(),
+        })
     }
     fn plugin_state(&self) -> mlua::Result<LuaPluginState> {
         let res = self.env.globals().raw_get::<_, LuaPluginState>(STATE_KEY);
         res
     }
-    fn start_inner(&mut self, _config: &Config) -> Result<(), Error> {
+    /// Runs the plugin file with the extra keys of its `[[plugin]]` block as
+    /// the `config` global, then calls the plugin's `setup` hook with them if
+    /// it has one.
+    pub fn start_inner(&mut self, _config: &Config) -> Result<(), Error> {
+        let _watch = self.watchdog.watch();
+        // Everything handed out by an earlier run is about to be replaced.
+        self.loaded = Callbacks::default();
+        self.queried = Callbacks::default();
+        self.previous = Callbacks::default();
+        self.env.expire_registry_values();
         let name = self.conf.name.as_deref().unwrap_or("");
+        let settings = self
+            .env
+            .to_value(&self.conf.settings)
+            .and_then(|settings| {
+                self.env.globals().set("config", settings.clone())?;
+                Ok(settings)
+            })
+            .with_context(|| format!("Error passing config to lua file plugin {}", name))?;
         let file = fs::read(&self.conf.file).with_context(|| {
             format!(
                 "Error reading lua file plugin {} at {}",
@@ -35,7 +82,9 @@ impl LuaPlugin {
                 self.conf.file.display()
             )
         })?;
-        let code = self.env.load(&file);
+        // With the `@` Lua reports errors as `path:line:`.
+        let chunk_name = format!("@{}", self.conf.file.display());
+        let code = self.env.load(&file).set_name(&chunk_name)?;
         code.exec().with_context(|| {
             format!(
                 "Error running lua file plugin {} at {}",
@@ -52,8 +101,47 @@ impl LuaPlugin {
                     self.conf.file.display()
                 )
             })?;
+        self.plugin_state()
+            .and_then(|state| state.setup(settings))
+            .with_context(|| {
+                format!(
+                    "Error in setup of lua file plugin {} at {}",
+                    name,
+                    self.conf.file.display()
+                )
+            })?;
         Ok(())
     }
+    pub fn next_inner(&mut self) -> mlua::Result<Poll<Option<ListEntry>>> {
+        if self.watchdog.tripped() {
+            return Ok(Poll::Ready(None));
+        }
+        let _watch = self.watchdog.watch();
+        let Self { env, loaded, .. } = self;
+        let mut found = Vec::new();
+        let ret = env
+            .globals()
+            .raw_get::<_, LuaPluginState>(STATE_KEY)
+            .and_then(|mut st| st.next(&mut found))?;
+        loaded.remember(env, found)?;
+        Ok(ret)
+    }
+    pub fn query_inner(&mut self, text: &str) -> mlua::Result<Vec<ListEntry>> {
+        if self.watchdog.tripped() {
+            return Ok(Vec::new());
+        }
+        let _watch = self.watchdog.watch();
+        self.previous = std::mem::take(&mut self.queried);
+        self.env.expire_registry_values();
+        let Self { env, queried, .. } = self;
+        let mut found = Vec::new();
+        let ret = env
+            .globals()
+            .raw_get::<_, LuaPluginState>(STATE_KEY)
+            .and_then(|st| st.query(text, &mut found))?;
+        queried.remember(env, found)?;
+        Ok(ret)
+    }
 }
 
 impl EntryPlugin for LuaPlugin {
@@ -72,19 +160,136 @@ impl EntryPlugin for LuaPlugin {
         }
         format!("{}", self.conf.file.display())
     }
+    /// Resumes the plugin until it has an entry ready; the launcher uses
+    /// `poll_next` instead.
     fn next(&mut self) -> Option<ListEntry> {
-        let raw = self.plugin_state().and_then(|mut st| st.next());
-        match raw {
+        loop {
+            if let Poll::Ready(ret) = self.poll_next() {
+                return ret;
+            }
+        }
+    }
+    fn poll_next(&mut self) -> Poll<Option<ListEntry>> {
+        match self.next_inner() {
             Ok(ret) => ret,
             Err(e) => {
                 eprintln!("Error from lua plugin {:?} : {:?}", self.name(), e);
-                None
+                Poll::Ready(None)
             }
         }
     }
+    fn query(&mut self, text: &str) -> Vec<ListEntry> {
+        match self.query_inner(text) {
+            Ok(ret) => ret,
+            Err(e) => {
+                eprintln!("Error from lua plugin {:?} : {:?}", self.name(), e);
+                Vec::new()
+            }
+        }
+    }
+    fn actions(&mut self, entry: &ListEntry) -> Vec<ListEntry> {
+        [&self.loaded, &self.queried, &self.previous]
+            .iter()
+            .find_map(|cbs| cbs.actions.get(&entry.tag?))
+            .cloned()
+            .unwrap_or_default()
+    }
+    fn select(&mut self, entry: &ListEntry) -> Option<Selected> {
+        if self.watchdog.tripped() {
+            return None;
+        }
+        let _watch = self.watchdog.watch();
+        let Self {
+            env,
+            loaded,
+            queried,
+            previous,
+            ..
+        } = self;
+        let key = [&*loaded, &*queried, &*previous]
+            .iter()
+            .find_map(|cbs| cbs.on_select.get(&entry.tag?))?;
+        let mut found = Vec::new();
+        let raw = env
+            .registry_value::<mlua::Function>(key)
+            .and_then(|func| func.call(()))
+            .and_then(|ret| parse_selected(ret, &mut found))
+            .and_then(|ret| {
+                queried.remember(env, found)?;
+                Ok(ret)
+            });
+        match raw {
+            Ok(ret) => Some(ret),
+            Err(e) => {
+                eprintln!("Error from lua plugin {:?} : {:?}", self.name(), e);
+                Some(Selected::Done(format!("Error in {}", self.name())))
+            }
+        }
+    }
+}
+
+/// The Lua functions attached to an entry through its `on_select` and
+/// `actions` fields.
+struct Callback<'lua> {
+    tag: u64,
+    on_select: Option<mlua::Function<'lua>>,
+    actions: Vec<ListEntry>,
+}
+
+/// The callbacks of the entries a plugin has handed out, keyed by the
+/// entries' tags so they can be found again when the user picks one.
+#[derive(Default)]
+struct Callbacks {
+    on_select: HashMap<u64, RegistryKey>,
+    actions: HashMap<u64, Vec<ListEntry>>,
+}
+
+impl Callbacks {
+    fn remember(&mut self, env: &Lua, found: Vec<Callback>) -> mlua::Result<()> {
+        for cb in found {
+            if let Some(func) = cb.on_select {
+                let key = env.create_registry_value(func)?;
+                self.on_select.insert(cb.tag, key);
+            }
+            if !cb.actions.is_empty() {
+                self.actions.insert(cb.tag, cb.actions);
+            }
+        }
+        Ok(())
+    }
 }
 
-fn parse_lua_entry(args: LuaValue) -> mlua::Result<ListEntry> {
+/// Turns what an `on_select` function returned into what the launcher does
+/// next: `nil` does nothing more, a string is copied to the clipboard, an
+/// entry is run in place of the selected one, and a list of entries is shown
+/// to pick from.
+fn parse_selected<'lua>(
+    ret: LuaValue<'lua>,
+    found: &mut Vec<Callback<'lua>>,
+) -> mlua::Result<Selected> {
+    match ret {
+        LuaValue::Nil => Ok(Selected::Done(String::new())),
+        LuaValue::String(text) => {
+            let text = text.to_str()?.to_owned();
+            Ok(Selected::Run(ListEntry {
+                display_name: Some(format!("Copy {}", text)),
+                exec_command: vec!["wl-copy".to_owned(), "--".to_owned(), text],
+                ..Default::default()
+            }))
+        }
+        LuaValue::Table(tbl) if tbl.raw_len() > 0 => tbl
+            .sequence_values::<LuaValue>()
+            .map(|ent| ent.and_then(|ent| parse_lua_entry(ent, found)))
+            .collect::<mlua::Result<Vec<_>>>()
+            .map(Selected::Entries),
+        other => parse_lua_entry(other, found).map(Selected::Run),
+    }
+}
+
+fn parse_lua_entry<'lua>(
+    args: LuaValue<'lua>,
+    found: &mut Vec<Callback<'lua>>,
+) -> mlua::Result<ListEntry> {
     let args = match args {
         LuaValue::Table(tbl) => tbl,
         other => {
@@ -95,22 +300,64 @@ fn parse_lua_entry(args: LuaValue) -> mlua::Result<ListEntry> {
             });
         }
     };
+    let location: Option<String> = args.raw_get(api::LOCATION_KEY)?;
+    parse_lua_table(args, found).map_err(|e| match location {
+        Some(location) => mlua::Error::RuntimeError(format!("{}: {}", location, e)),
+        None => e,
+    })
+}
+
+fn parse_lua_table<'lua>(
+    args: mlua::Table<'lua>,
+    found: &mut Vec<Callback<'lua>>,
+) -> mlua::Result<ListEntry> {
     let display_name: Option<String> = args.raw_get("name")?;
     let search_terms: Vec<String> = args.raw_get("search_terms")?;
-    let exec_flags = args.raw_get("exec_flags").and_then(parse_lua_exec_flags)?;
+    let mut exec_flags = args.raw_get("exec_flags").and_then(parse_lua_exec_flags)?;
     let raw_children: Option<Vec<LuaValue>> = args.raw_get("children")?;
     let children = raw_children
         .into_iter()
         .flat_map(|c| c.into_iter())
-        .map(parse_lua_entry)
+        .map(|c| parse_lua_entry(c, found))
         .collect::<Result<Vec<_>, _>>()?;
-    let exec_command = parse_command_string(&(args.raw_get::<_, String>("exec")?));
+    let on_select: Option<mlua::Function> = args.raw_get("on_select")?;
+    let raw_actions: Option<Vec<LuaValue>> = args.raw_get("actions")?;
+    let actions = raw_actions
+        .into_iter()
+        .flat_map(|a| a.into_iter())
+        .map(|a| parse_lua_entry(a, found))
+        .collect::<Result<Vec<_>, _>>()?;
+    let exec_command = match args.raw_get::<_, Option<String>>("exec")? {
+        Some(exec) => parse_command_string(&exec),
+        None if on_select.is_some() => Vec::new(),
+        None => {
+            return Err(mlua::Error::FromLuaConversionError {
+                from: "table",
+                to: "tmpas::ListEntry",
+                message: Some("Entry needs either exec or on_select!".into()),
+            });
+        }
+    };
+    exec_flags.set_plugin_handled(on_select.is_some());
+    let tag = if on_select.is_some() || !actions.is_empty() {
+        let tag = next_tag();
+        found.push(Callback {
+            tag,
+            on_select,
+            actions,
+        });
+        Some(tag)
+    } else {
+        None
+    };
     Ok(ListEntry {
         display_name,
         exec_command,
         exec_flags,
         children,
         search_terms,
+        source: None,
+        tag,
     })
 }
 
@@ -197,9 +444,12 @@ fn parse_lua_exec_flags(args: LuaValue) -> mlua::Result<RunFlags> {
             "should_fork" => {
                 retvl.set_should_fork(val);
             }
+            "keep_open" => {
+                retvl.set_keep_open(val);
+            }
             other => {
                 let msg = format!(
-                    "Field {} is not in the allowed field list: [\"is_term\", \"should_fork\"]",
+                    "Field {} is not in the allowed field list: [\"is_term\", \"should_fork\", \"keep_open\"]",
                     other
                 );
                 return Err(mlua::Error::FromLuaConversionError {
@@ -213,14 +463,20 @@ fn parse_lua_exec_flags(args: LuaValue) -> mlua::Result<RunFlags> {
     Ok(retvl)
 }
 
-#[derive(Debug)]
 struct LuaPluginState<'a> {
+    env: &'a Lua,
     inner: Option<mlua::Table<'a>>,
 }
 
+/// Where the plugin is in producing its entries, kept in its table under
+/// `__NEXT_FLAG`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum PluginStateNext {
+    /// Calling `next` in a fresh coroutine.
     Function,
+    /// `next` yielded, and its coroutine, kept under `__NEXT_THREAD`, is
+    /// resumed instead of calling it again.
+    Coroutine,
     Table(u16),
     End,
 }
@@ -232,16 +488,32 @@ impl<'a> LuaPluginState<'a> {
             .and_then(|tbl| tbl.raw_get::<_, Option<String>>("name").ok())
             .flatten()
     }
-    pub fn next(&mut self) -> mlua::Result<Option<ListEntry>> {
-        if let Some(nextfn) = self.nextfn() {
-            let output = nextfn
-                .call::<_, Option<LuaValue>>(())?
-                .map(parse_lua_entry)
-                .transpose()?;
-            if let Some(res) = output {
-                return Ok(Some(res));
+    /// `next` runs as a coroutine. Returning an entry or yielding one both
+    /// hand it out, but after a yield the coroutine picks up where it left
+    /// off on the following call instead of `next` being called afresh. A
+    /// bare `coroutine.yield()` means no entry is ready yet. Once `next`
+    /// returns `nil` the `entries` table is used.
+    pub fn next(
+        &mut self,
+        found: &mut Vec<Callback<'a>>,
+    ) -> mlua::Result<Poll<Option<ListEntry>>> {
+        if let Some(thread) = self.next_thread()? {
+            let output = thread.resume::<_, Option<LuaValue>>(());
+            let yielded = output.is_ok() && thread.status() == ThreadStatus::Resumable;
+            if yielded {
+                self.set_flag(PluginStateNext::Coroutine)?;
+                if let Some(inner) = self.inner.as_ref() {
+                    inner.raw_set("__NEXT_THREAD", thread)?;
+                }
             } else {
-                self.set_flag(PluginStateNext::Table(0))?;
+                self.set_flag(PluginStateNext::Function)?;
+            }
+            match output?.map(|ent| parse_lua_entry(ent, found)).transpose()? {
+                Some(res) => return Ok(Poll::Ready(Some(res))),
+                None if yielded => return Ok(Poll::Pending),
+                None => {
+                    self.set_flag(PluginStateNext::Table(0))?;
+                }
             }
         } else if matches!(self.next_flag(), PluginStateNext::Function) {
             self.set_flag(PluginStateNext::Table(0))?;
@@ -251,26 +523,60 @@ impl<'a> LuaPluginState<'a> {
                 Some(tbl) => tbl,
                 None => {
                     self.set_flag(PluginStateNext::End)?;
-                    return Ok(None);
+                    return Ok(Poll::Ready(None));
                 }
             };
             let res = inner
                 .raw_get::<_, Option<mlua::Table>>("entries")?
                 .and_then(|tbl| tbl.get::<_, Option<mlua::Value>>(next_idx + 1).transpose())
-                .map(|res| res.and_then(parse_lua_entry))
+                .map(|res| res.and_then(|ent| parse_lua_entry(ent, found)))
                 .transpose()?;
             match res {
                 Some(out) => {
                     self.set_flag(PluginStateNext::Table(next_idx + 1))?;
-                    return Ok(Some(out));
+                    return Ok(Poll::Ready(Some(out)));
                 }
                 None => {
                     self.set_flag(PluginStateNext::End)?;
-                    return Ok(None);
+                    return Ok(Poll::Ready(None));
                 }
             }
         }
-        Ok(None)
+        Ok(Poll::Ready(None))
+    }
+
+    pub fn query(
+        &self,
+        text: &str,
+        found: &mut Vec<Callback<'a>>,
+    ) -> mlua::Result<Vec<ListEntry>> {
+        let queryfn = match self.inner.as_ref() {
+            Some(tbl) => tbl.get::<_, Option<mlua::Function>>("on_query")?,
+            None => None,
+        };
+        let queryfn = match queryfn {
+            Some(f) => f,
+            None => {
+                return Ok(Vec::new());
+            }
+        };
+        queryfn
+            .call::<_, Option<Vec<LuaValue>>>(text)?
+            .into_iter()
+            .flat_map(|v| v.into_iter())
+            .map(|ent| parse_lua_entry(ent, found))
+            .collect()
+    }
+
+    pub fn setup(&self, settings: LuaValue<'a>) -> mlua::Result<()> {
+        let setupfn = match self.inner.as_ref() {
+            Some(tbl) => tbl.get::<_, Option<mlua::Function>>("setup")?,
+            None => None,
+        };
+        match setupfn {
+            Some(f) => f.call(settings),
+            None => Ok(()),
+        }
     }
 
     fn next_flag(&self) -> PluginStateNext {
@@ -289,25 +595,42 @@ impl<'a> LuaPluginState<'a> {
                 let n = (n % i64::from(u16::max_value())) as u16;
                 PluginStateNext::Table(n)
             }
+            LuaValue::Boolean(true) => PluginStateNext::Coroutine,
             _ => PluginStateNext::End,
         }
     }
     fn set_flag(&mut self, flag: PluginStateNext) -> mlua::Result<()> {
         if let Some(inner) = self.inner.as_ref() {
+            if flag != PluginStateNext::Coroutine {
+                inner.raw_remove("__NEXT_THREAD")?;
+            }
             match flag {
                 PluginStateNext::End => inner.raw_set("__NEXT_FLAG", false),
                 PluginStateNext::Function => inner.raw_remove("__NEXT_FLAG"),
+                PluginStateNext::Coroutine => inner.raw_set("__NEXT_FLAG", true),
                 PluginStateNext::Table(n) => inner.raw_set("__NEXT_FLAG", n),
             }
         } else {
             Ok(())
         }
     }
-    fn nextfn(&self) -> Option<mlua::Function<'a>> {
-        if !matches!(self.next_flag(), PluginStateNext::Function) {
-            return None;
+    /// The coroutine to resume for the next entry: the suspended one, or a
+    /// fresh one running `next`.
+    fn next_thread(&self) -> mlua::Result<Option<mlua::Thread<'a>>> {
+        let inner = match self.inner.as_ref() {
+            Some(tbl) => tbl,
+            None => {
+                return Ok(None);
+            }
+        };
+        match self.next_flag() {
+            PluginStateNext::Coroutine => inner.raw_get("__NEXT_THREAD"),
+            PluginStateNext::Function => match inner.get::<_, Option<mlua::Function>>("next") {
+                Ok(Some(nextfn)) => self.env.create_thread(nextfn).map(Some),
+                _ => Ok(None),
+            },
+            _ => Ok(None),
         }
-        self.inner.as_ref()?.get("next").ok().flatten()
     }
     fn verify(&self) -> mlua::Result<()> {
         let inner = match self.inner.as_ref() {
@@ -321,16 +644,18 @@ impl<'a> LuaPluginState<'a> {
         raw_entries
             .into_iter()
             .flat_map(|v| v.into_iter())
-            .map(parse_lua_entry)
+            .map(|ent| parse_lua_entry(ent, &mut Vec::new()))
             .find(|res| res.is_err())
             .transpose()?;
         inner.raw_get::<_, Option<mlua::Function>>("next")?;
+        inner.raw_get::<_, Option<mlua::Function>>("on_query")?;
+        inner.raw_get::<_, Option<mlua::Function>>("setup")?;
         Ok(())
     }
 }
 
 impl<'lua> FromLua<'lua> for LuaPluginState<'lua> {
-    fn from_lua(lua_value: LuaValue<'lua>, _: &'lua Lua) -> mlua::Result<Self> {
+    fn from_lua(lua_value: LuaValue<'lua>, env: &'lua Lua) -> mlua::Result<Self> {
         let inner = match lua_value {
             LuaValue::Table(tbl) => Some(tbl),
             LuaValue::Nil => None,
@@ -342,7 +667,7 @@ impl<'lua> FromLua<'lua> for LuaPluginState<'lua> {
                 });
             }
         };
-        let retvl = Self { inner };
+        let retvl = Self { env, inner };
         Ok(retvl)
     }
 }
@@ -350,6 +675,178 @@ impl<'lua> FromLua<'lua> for LuaPluginState<'lua> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::plugins::loadable::lua_plugin;
+
+    #[test]
+    fn on_query() {
+        let env = Lua::new();
+        api::register(&env).unwrap();
+        env.load(
+            r#"
+            plugin {
+                name = "Echo",
+                on_query = function(text)
+                    return { entry { name = "echo " .. text, exec = "echo " .. text } }
+                end
+            }
+        "#,
+        )
+        .exec()
+        .unwrap();
+        let state: LuaPluginState = env.globals().raw_get(STATE_KEY).unwrap();
+        state.verify().unwrap();
+        let res = state.query("hi", &mut Vec::new()).unwrap();
+        assert_eq!(1, res.len());
+        assert_eq!("echo hi", res[0].name());
+        assert_eq!(vec!["echo".to_owned(), "hi".to_owned()], res[0].exec_command);
+    }
+    #[test]
+    fn select_callbacks() {
+        let mut plugin = lua_plugin(
+            r##"
+            local picked = 0
+            plugin {
+                name = "Callbacks",
+                entries = {
+                    entry {
+                        name = "Colors",
+                        on_select = function()
+                            return {
+                                entry { name = "Red", on_select = function() return "#ff0000" end },
+                                entry { name = "Blue", on_select = function() picked = picked + 1 end },
+                            }
+                        end,
+                        actions = {
+                            entry { name = "Open palette", on_select = function()
+                                return entry { name = "Palette", exec = "palette --count " .. picked }
+                            end },
+                        },
+                    },
+                    entry { name = "Same", on_select = function() return "first" end },
+                    entry { name = "Same", on_select = function() return "second" end },
+                },
+            }
+        "##,
+            "",
+        );
+
+        let colors = plugin.next().unwrap();
+        assert!(colors.exec_command.is_empty());
+        assert!(colors.exec_flags.plugin_handled());
+        let shades = match plugin.select(&colors) {
+            Some(Selected::Entries(shades)) => shades,
+            other => panic!("Expected entries, got {:?}", other),
+        };
+        assert_eq!(
+            Some(Selected::Run(ListEntry {
+                display_name: Some("Copy #ff0000".to_owned()),
+                exec_command: vec!["wl-copy".to_owned(), "--".to_owned(), "#ff0000".to_owned()],
+                ..Default::default()
+            })),
+            plugin.select(&shades[0])
+        );
+        assert_eq!(Some(Selected::Done(String::new())), plugin.select(&shades[1]));
+
+        let actions = plugin.actions(&colors);
+        assert_eq!(1, actions.len());
+        match plugin.select(&actions[0]) {
+            Some(Selected::Run(palette)) => {
+                assert_eq!(vec!["palette", "--count", "1"], palette.exec_command)
+            }
+            other => panic!("Expected an entry to run, got {:?}", other),
+        }
+        assert_eq!(None, plugin.select(&ListEntry::default()));
+
+        let first = plugin.next().unwrap();
+        let second = plugin.next().unwrap();
+        assert_ne!(first, second);
+        match (plugin.select(&first), plugin.select(&second)) {
+            (Some(Selected::Run(first)), Some(Selected::Run(second))) => {
+                assert_eq!(Some("Copy first"), first.display_name.as_deref());
+                assert_eq!(Some("Copy second"), second.display_name.as_deref());
+            }
+            other => panic!("Expected entries to run, got {:?}", other),
+        }
+    }
+    #[test]
+    fn plugin_settings() {
+        let mut plugin = lua_plugin(
+            r#"
+            local root = config.rom_root
+            plugin {
+                name = "Settings",
+                setup = function(conf)
+                    root = root .. "|" .. conf.extensions[2]
+                end,
+                next = function()
+                    local ent = root and entry { name = root, exec = "true" }
+                    root = nil
+                    return ent
+                end
+            }
+        "#,
+            "rom_root = '~/Games/roms'\nextensions = ['sfc', 'smc']\n",
+        );
+        assert_eq!(2, plugin.conf.settings.len());
+        assert_eq!(
+            Some("~/Games/roms|smc"),
+            plugin.next().as_ref().map(|ent| ent.name())
+        );
+        assert_eq!(None, plugin.next());
+    }
+    #[test]
+    fn coroutine_next() {
+        const PLUGIN: &str = r#"
+            local scanned = false
+            plugin {
+                name = "Coroutine",
+                entries = { entry { name = "from table", exec = "true" } },
+                next = function()
+                    if scanned then
+                        return nil
+                    end
+                    for _, name in ipairs({ "a", "b" }) do
+                        coroutine.yield()
+                        coroutine.yield(entry { name = name, exec = "echo " .. name })
+                    end
+                    scanned = true
+                    local last = entry { name = "last", exec = "true" }
+                    if config.spin then
+                        coroutine.yield()
+                        while true do end
+                    end
+                    return last
+                end,
+            }
+        "#;
+        let mut plugin = lua_plugin(PLUGIN, "time_limit_ms = 50\nspin = false\n");
+        let mut spinning = lua_plugin(PLUGIN, "time_limit_ms = 50\nspin = true\n");
+
+        let polled: Vec<_> = std::iter::from_fn(|| match plugin.poll_next() {
+            Poll::Ready(None) => None,
+            Poll::Ready(Some(ent)) => Some(Some(ent.name().to_owned())),
+            Poll::Pending => Some(None),
+        })
+        .collect();
+        let expected = vec![
+            None,
+            Some("a"),
+            None,
+            Some("b"),
+            Some("last"),
+            Some("from table"),
+        ];
+        assert_eq!(
+            expected,
+            polled.iter().map(|name| name.as_deref()).collect::<Vec<_>>()
+        );
+
+        assert_eq!(Some("a"), spinning.next().as_ref().map(|ent| ent.name()));
+        assert_eq!(Some("b"), spinning.next().as_ref().map(|ent| ent.name()));
+        assert_eq!(Poll::Pending, spinning.poll_next());
+        assert_eq!(Poll::Ready(None), spinning.poll_next());
+        assert!(spinning.watchdog.tripped());
+    }
     #[test]
     fn parse_cmd() {
         let simple = "/usr/bin/cat mout.txt";
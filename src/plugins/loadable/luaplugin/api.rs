@@ -1,12 +1,49 @@
-use mlua::{Lua, Value as LuaValue};
+use crate::plugins::builtins::{
+    parse_sections, xdg_config_home, xdg_data_dirs, xdg_data_homes, Section,
+};
+use crate::utils::expand_tilde;
+
+use mlua::{Lua, LuaSerdeExt, Value as LuaValue};
+
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::process::Command;
+use std::time::UNIX_EPOCH;
 
 pub const STATE_KEY: &str = "__PLUGIN_STATE__";
 
+/// The key under which `entry` records where it was called, so that
+/// `parse_lua_entry` can point at the right line.
+pub const LOCATION_KEY: &str = "__location";
+
+/// `entry` raises its errors from Lua, which LuaJIT can unwind and which
+/// blames the line that called it. The location lookup is kept as an upvalue
+/// since the sandbox takes the debug library away later.
+const ENTRY: &str = r#"
+local try_entry, location_key = ...
+local getinfo = debug and debug.getinfo
+function entry(args)
+    local retvl, err = try_entry(args)
+    if err then
+        error(err, 2)
+    end
+    -- A tail call like `return entry(...)` leaves no caller to blame.
+    local info = getinfo and getinfo(2, "Sl")
+    if info and info.currentline > 0 then
+        retvl[location_key] = info.short_src .. ":" .. info.currentline
+    end
+    return retvl
+end
+"#;
+
 pub fn register(env: &Lua) -> mlua::Result<()> {
-    env.globals()
-        .set("entry", env.create_function(lua_entry_cb)?)?;
+    env.load(ENTRY)
+        .set_name("entry")?
+        .call::<_, ()>((env.create_function(lua_try_entry)?, LOCATION_KEY))?;
     env.globals()
         .set("plugin", env.create_function(lua_plugin_cb)?)?;
+    register_tmpas(env)?;
     Ok(())
 }
 
@@ -15,8 +52,23 @@ fn lua_plugin_cb<'lua>(state: &'lua Lua, args: mlua::Table) -> mlua::Result<()>
     Ok(())
 }
 
+fn lua_try_entry<'a>(
+    state: &'a Lua,
+    args: LuaValue<'a>,
+) -> mlua::Result<Fallible<mlua::Table<'a>>> {
+    Ok(fallible(lua_entry_cb(state, args)))
+}
+
 fn lua_entry_cb<'a>(state: &'a Lua, args: LuaValue<'a>) -> mlua::Result<mlua::Table<'a>> {
-    let allowed_keys = ["search_terms", "children", "exec_flags", "name", "exec"];
+    let allowed_keys = [
+        "search_terms",
+        "children",
+        "exec_flags",
+        "name",
+        "exec",
+        "on_select",
+        "actions",
+    ];
 
     let retvl = state.create_table()?;
     retvl.raw_set("search_terms", state.create_table()?)?;
@@ -25,6 +77,7 @@ fn lua_entry_cb<'a>(state: &'a Lua, args: LuaValue<'a>) -> mlua::Result<mlua::Ta
     let run_flags = state.create_table()?;
     run_flags.raw_set("is_term", false)?;
     run_flags.raw_set("should_fork", false)?;
+    run_flags.raw_set("keep_open", false)?;
     retvl.raw_set("exec_flags", run_flags)?;
 
     match args {
@@ -41,10 +94,11 @@ fn lua_entry_cb<'a>(state: &'a Lua, args: LuaValue<'a>) -> mlua::Result<mlua::Ta
                 }
                 retvl.raw_set(key, val)?;
             }
-            let is_okay = matches!(retvl.raw_get("exec")?, LuaValue::String(_));
+            let is_okay = matches!(retvl.raw_get("exec")?, LuaValue::String(_))
+                || matches!(retvl.raw_get("on_select")?, LuaValue::Function(_));
             if !is_okay {
                 let msg =
-                    "Error: cannot convert args to entry: Required field \"exec\" is missing."
+                    "Error: cannot convert args to entry: Required field \"exec\" or \"on_select\" is missing."
                         .to_owned();
                 return Err(mlua::Error::RuntimeError(msg));
             }
@@ -56,3 +110,298 @@ fn lua_entry_cb<'a>(state: &'a Lua, args: LuaValue<'a>) -> mlua::Result<mlua::Ta
         ))),
     }
 }
+
+/// Functions that can fail return `nil` plus an error message, like Lua's own
+/// `io` library, so that scripts can decide how much they care.
+type Fallible<T> = (Option<T>, Option<String>);
+
+fn fallible<T, E: Display>(res: Result<T, E>) -> Fallible<T> {
+    match res {
+        Ok(val) => (Some(val), None),
+        Err(e) => (None, Some(e.to_string())),
+    }
+}
+
+/// Registers the `tmpas` table of helpers, also available through
+/// `require('tmpas')`.
+fn register_tmpas(env: &Lua) -> mlua::Result<()> {
+    let fs_tbl = env.create_table()?;
+    fs_tbl.set("read", env.create_function(lua_fs_read)?)?;
+    fs_tbl.set("read_dir", env.create_function(lua_fs_read_dir)?)?;
+    fs_tbl.set("stat", env.create_function(lua_fs_stat)?)?;
+    fs_tbl.set(
+        "exists",
+        env.create_function(|_, path: String| Ok(expand_tilde(&path).exists()))?,
+    )?;
+    fs_tbl.set("glob", env.create_function(lua_fs_glob)?)?;
+
+    let env_tbl = env.create_table()?;
+    env_tbl.set(
+        "get",
+        env.create_function(|_, name: String| Ok(env::var(name).ok()))?,
+    )?;
+
+    let xdg_tbl = env.create_table()?;
+    xdg_tbl.set(
+        "config_home",
+        env.create_function(|_, ()| Ok(xdg_config_home().map(|dir| dir.display().to_string())))?,
+    )?;
+    xdg_tbl.set(
+        "data_dirs",
+        env.create_function(|_, ()| {
+            let dirs: Vec<String> = xdg_data_homes()
+                .chain(xdg_data_dirs())
+                .map(|dir| dir.display().to_string())
+                .collect();
+            Ok(dirs)
+        })?,
+    )?;
+
+    let exec_tbl = env.create_table()?;
+    exec_tbl.set("capture", env.create_function(lua_exec_capture)?)?;
+
+    let parse_tbl = env.create_table()?;
+    parse_tbl.set("ini", env.create_function(lua_parse_ini)?)?;
+    parse_tbl.set("desktop", env.create_function(lua_parse_desktop)?)?;
+    parse_tbl.set(
+        "json",
+        env.create_function(|lua, raw: String| {
+            let res = serde_json::from_str::<serde_json::Value>(&raw).map_err(|e| e.to_string());
+            Ok(fallible(res.and_then(|val| {
+                lua.to_value(&val).map_err(|e| e.to_string())
+            })))
+        })?,
+    )?;
+    parse_tbl.set(
+        "toml",
+        env.create_function(|lua, raw: String| {
+            let res = toml::from_str::<toml::Value>(&raw).map_err(|e| e.to_string());
+            Ok(fallible(res.and_then(|val| {
+                lua.to_value(&val).map_err(|e| e.to_string())
+            })))
+        })?,
+    )?;
+
+    let tmpas = env.create_table()?;
+    tmpas.set("fs", fs_tbl)?;
+    tmpas.set("env", env_tbl)?;
+    tmpas.set("xdg", xdg_tbl)?;
+    tmpas.set("exec", exec_tbl)?;
+    tmpas.set("parse", parse_tbl)?;
+    env.globals().set("tmpas", tmpas.clone())?;
+    let loaded: Option<mlua::Table> = env
+        .globals()
+        .get::<_, Option<mlua::Table>>("package")?
+        .map(|package| package.get("loaded"))
+        .transpose()?;
+    if let Some(loaded) = loaded {
+        loaded.set("tmpas", tmpas)?;
+    }
+    Ok(())
+}
+
+fn lua_fs_read(_: &Lua, path: String) -> mlua::Result<Fallible<String>> {
+    Ok(fallible(fs::read_to_string(expand_tilde(&path))))
+}
+
+fn file_type_name(file_type: fs::FileType) -> &'static str {
+    if file_type.is_symlink() {
+        "symlink"
+    } else if file_type.is_dir() {
+        "dir"
+    } else if file_type.is_file() {
+        "file"
+    } else {
+        "other"
+    }
+}
+
+/// Lists a directory as `{ name, path, type }` tables sorted by name. The type
+/// is that of the entry itself, so symlinks show up as `"symlink"`.
+fn lua_fs_read_dir<'lua>(
+    lua: &'lua Lua,
+    path: String,
+) -> mlua::Result<Fallible<mlua::Table<'lua>>> {
+    let read = match fs::read_dir(expand_tilde(&path)) {
+        Ok(read) => read,
+        Err(e) => {
+            return Ok((None, Some(e.to_string())));
+        }
+    };
+    let mut entries: Vec<_> = read.filter_map(|ent| ent.ok()).collect();
+    entries.sort_by_key(|ent| ent.file_name());
+    let retvl = lua.create_table()?;
+    for (idx, ent) in entries.into_iter().enumerate() {
+        let tbl = lua.create_table()?;
+        tbl.set("name", ent.file_name().to_string_lossy().into_owned())?;
+        tbl.set("path", ent.path().display().to_string())?;
+        let kind = ent.file_type().map(file_type_name).unwrap_or("other");
+        tbl.set("type", kind)?;
+        retvl.set(idx + 1, tbl)?;
+    }
+    Ok((Some(retvl), None))
+}
+
+/// Describes a file as `{ type, size, modified, readonly }`, following
+/// symlinks. `modified` is in seconds since the Unix epoch.
+fn lua_fs_stat<'lua>(lua: &'lua Lua, path: String) -> mlua::Result<Fallible<mlua::Table<'lua>>> {
+    let meta = match fs::metadata(expand_tilde(&path)) {
+        Ok(meta) => meta,
+        Err(e) => {
+            return Ok((None, Some(e.to_string())));
+        }
+    };
+    let retvl = lua.create_table()?;
+    retvl.set("type", file_type_name(meta.file_type()))?;
+    retvl.set("size", meta.len())?;
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|dur| dur.as_secs());
+    retvl.set("modified", modified)?;
+    retvl.set("readonly", meta.permissions().readonly())?;
+    Ok((Some(retvl), None))
+}
+
+fn lua_fs_glob(_: &Lua, pattern: String) -> mlua::Result<Fallible<Vec<String>>> {
+    let pattern = expand_tilde(&pattern);
+    let res = glob::glob(&pattern.to_string_lossy()).map(|paths| {
+        paths
+            .filter_map(|res| res.ok())
+            .map(|path| path.display().to_string())
+            .collect()
+    });
+    Ok(fallible(res))
+}
+
+/// Runs a command given as an argv array, without a shell in between, and
+/// returns `{ stdout, stderr, status }`. `status` is `nil` if the command was
+/// killed by a signal.
+fn lua_exec_capture<'lua>(
+    lua: &'lua Lua,
+    argv: Vec<String>,
+) -> mlua::Result<Fallible<mlua::Table<'lua>>> {
+    let (program, args) = match argv.split_first() {
+        Some(split) => split,
+        None => {
+            return Ok((None, Some("exec.capture needs a command to run".to_owned())));
+        }
+    };
+    let output = match Command::new(program).args(args).output() {
+        Ok(output) => output,
+        Err(e) => {
+            return Ok((None, Some(format!("Could not run {}: {}", program, e))));
+        }
+    };
+    let retvl = lua.create_table()?;
+    retvl.set("stdout", lua.create_string(&output.stdout)?)?;
+    retvl.set("stderr", lua.create_string(&output.stderr)?)?;
+    retvl.set("status", output.status.code())?;
+    Ok((Some(retvl), None))
+}
+
+/// Turns a section's fields into a table, with localised variants stored
+/// under their full keys such as `Name[de]`.
+fn section_table<'lua>(lua: &'lua Lua, section: &Section) -> mlua::Result<mlua::Table<'lua>> {
+    let retvl = lua.create_table()?;
+    for (key, field) in section.fields.iter() {
+        if let Some(default) = field.default.as_ref() {
+            retvl.set(key.as_str(), default.as_str())?;
+        }
+        for (attribute, value) in field.attributes.iter() {
+            retvl.set(format!("{}[{}]", key, attribute), value.as_str())?;
+        }
+    }
+    Ok(retvl)
+}
+
+/// Parses INI text into a table of sections by header. Keys before the first
+/// header end up in the section named `""`.
+fn lua_parse_ini<'lua>(lua: &'lua Lua, raw: String) -> mlua::Result<mlua::Table<'lua>> {
+    let retvl = lua.create_table()?;
+    for section in parse_sections(&raw) {
+        retvl.set(section.header.as_str(), section_table(lua, &section)?)?;
+    }
+    Ok(retvl)
+}
+
+/// Parses a desktop entry into the fields of its `[Desktop Entry]` section,
+/// with its `[Desktop Action <id>]` sections under `actions` by id.
+fn lua_parse_desktop<'lua>(
+    lua: &'lua Lua,
+    raw: String,
+) -> mlua::Result<Fallible<mlua::Table<'lua>>> {
+    let sections = parse_sections(&raw);
+    let main = match sections.iter().find(|s| s.header == "Desktop Entry") {
+        Some(main) => main,
+        None => {
+            return Ok((None, Some("No [Desktop Entry] section".to_owned())));
+        }
+    };
+    let retvl = section_table(lua, main)?;
+    let actions = lua.create_table()?;
+    for section in sections.iter() {
+        if let Some(id) = section.header.strip_prefix("Desktop Action ") {
+            actions.set(id, section_table(lua, section)?)?;
+        }
+    }
+    retvl.set("actions", actions)?;
+    Ok((Some(retvl), None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    #[test]
+    fn tmpas_module() {
+        let dir = TempDir::new("lua-api");
+        fs::create_dir_all(dir.join("sub dir")).unwrap();
+        fs::write(dir.join("my file.txt"), "hello").unwrap();
+
+        let env = Lua::new();
+        register(&env).unwrap();
+        env.globals().set("DIR", dir.display().to_string()).unwrap();
+        env.load(
+            r#"
+            local tmpas = require('tmpas')
+            local listing = tmpas.fs.read_dir(DIR)
+            assert(#listing == 2)
+            assert(listing[1].name == "my file.txt" and listing[1].type == "file")
+            assert(listing[2].name == "sub dir" and listing[2].type == "dir")
+            assert(tmpas.fs.stat(DIR .. "/my file.txt").size == 5)
+            assert(tmpas.fs.exists(DIR .. "/sub dir"))
+            assert(#tmpas.fs.glob(DIR .. "/*.txt") == 1)
+            local missing, err = tmpas.fs.read_dir(DIR .. "/missing")
+            assert(missing == nil and err ~= nil)
+
+            local out = tmpas.exec.capture({ "printf", "%s", "a b; c" })
+            assert(out.stdout == "a b; c" and out.status == 0)
+
+            local desktop = tmpas.parse.desktop([[
+[Desktop Entry]
+Name=Files
+Name[de]=Dateien
+Exec=nautilus %U
+Actions=new-window;
+
+[Desktop Action new-window]
+Name=New Window
+Exec=nautilus --new-window
+]])
+            assert(desktop.Name == "Files" and desktop["Name[de]"] == "Dateien")
+            assert(desktop.actions["new-window"].Exec == "nautilus --new-window")
+            assert(tmpas.parse.ini("top=1\n[a]\nkey=value")["a"].key == "value")
+
+            assert(tmpas.parse.json('{"items": [1, 2]}').items[2] == 2)
+            assert(tmpas.parse.toml('[table]\nkey = "value"').table.key == "value")
+            local bad, err = tmpas.parse.json("{")
+            assert(bad == nil and err ~= nil)
+        "#,
+        )
+        .exec()
+        .unwrap();
+    }
+}
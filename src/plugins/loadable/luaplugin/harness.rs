@@ -0,0 +1,325 @@
+//! `tmpas plugin-test`: runs a plugin outside the launcher and reports what
+//! it produces, so that plugin authors do not have to dig through the
+//! launcher's log.
+
+use super::super::{LuaConfig, PluginTest};
+use super::LuaPlugin;
+use crate::config::Config;
+use crate::model::{EntryPlugin, ListEntry};
+
+use anyhow::{Context, Error};
+use serde::Serialize;
+
+use std::fs;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default, Serialize)]
+struct Report {
+    plugin: String,
+    start_ms: f64,
+    entries: Vec<Produced>,
+    /// The time spent in `next` over all entries.
+    next_ms: f64,
+    /// How often `next` yielded without an entry.
+    pending: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    query: Option<QueryReport>,
+    errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Produced {
+    #[serde(flatten)]
+    entry: EntryReport,
+    ms: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct QueryReport {
+    text: String,
+    ms: f64,
+    entries: Vec<EntryReport>,
+}
+
+#[derive(Debug, Serialize)]
+struct EntryReport {
+    name: String,
+    exec: Vec<String>,
+    flags: Vec<&'static str>,
+    search_terms: Vec<String>,
+    children: Vec<EntryReport>,
+}
+
+impl From<&ListEntry> for EntryReport {
+    fn from(ent: &ListEntry) -> Self {
+        let flags = &ent.exec_flags;
+        let flags = [
+            (flags.is_term(), "is_term"),
+            (flags.should_fork(), "should_fork"),
+            (flags.keep_open(), "keep_open"),
+            (flags.needs_confirm(), "confirm"),
+            (flags.plugin_handled(), "on_select"),
+        ];
+        Self {
+            name: ent.name().to_owned(),
+            exec: ent.exec_command.clone(),
+            flags: flags
+                .iter()
+                .filter(|(set, _)| *set)
+                .map(|(_, name)| *name)
+                .collect(),
+            search_terms: ent.search_terms.clone(),
+            children: ent.children.iter().map(EntryReport::from).collect(),
+        }
+    }
+}
+
+pub fn run(args: &PluginTest) -> i32 {
+    let plugin = load_config(args).and_then(|conf| {
+        LuaPlugin::new(conf).with_context(|| format!("Error loading {}", args.file.display()))
+    });
+    let report = match plugin {
+        Ok(mut plugin) => collect(&mut plugin, args),
+        Err(e) => {
+            eprintln!("ERROR: {:#}", e);
+            return 1;
+        }
+    };
+    if args.json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("ERROR: Could not write the report: {}", e),
+        }
+    } else {
+        print_tree(&report);
+    }
+    if report.errors.is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+/// Builds the plugin's config as if it had a `[[plugin]]` block made of the
+/// settings file plus its path.
+fn load_config(args: &PluginTest) -> Result<LuaConfig, Error> {
+    let mut block = match &args.settings {
+        Some(path) => {
+            let raw = fs::read_to_string(path)
+                .with_context(|| format!("Error reading settings {}", path.display()))?;
+            toml::from_str(&raw)
+                .with_context(|| format!("Error parsing settings {}", path.display()))?
+        }
+        None => toml::value::Table::new(),
+    };
+    let file = args.file.display().to_string();
+    block.insert("file".to_owned(), toml::Value::String(file));
+    toml::Value::Table(block)
+        .try_into()
+        .context("Error in plugin settings")
+}
+
+/// Starts the plugin, drains it and then queries it, stopping at the first
+/// error since the launcher would have given up on the plugin there too.
+/// Running out of `--timeout-ms` while draining counts as an error, since
+/// the plugin would never stop otherwise.
+fn collect(plugin: &mut LuaPlugin, args: &PluginTest) -> Report {
+    let mut report = Report::default();
+    let (res, elapsed) = timed(|| plugin.start_inner(&Config::default()));
+    report.plugin = plugin.name();
+    report.start_ms = millis(elapsed);
+    if let Err(e) = res {
+        report.errors.push(format!("{:#}", e));
+        return report;
+    }
+    // The time spent on the entry so far, counting resumes that had
+    // nothing ready.
+    let mut waited = Duration::default();
+    let deadline = Instant::now() + Duration::from_millis(args.timeout_ms);
+    while args.limit.is_none_or(|limit| report.entries.len() < limit) {
+        if Instant::now() >= deadline {
+            report.errors.push(format!(
+                "Still producing entries after {} ms; use --limit if that is expected",
+                args.timeout_ms
+            ));
+            return report;
+        }
+        let (res, elapsed) = timed(|| plugin.next_inner());
+        report.next_ms += millis(elapsed);
+        waited += elapsed;
+        match res {
+            Ok(Poll::Ready(Some(ent))) => {
+                report.entries.push(Produced {
+                    entry: EntryReport::from(&ent),
+                    ms: millis(std::mem::take(&mut waited)),
+                });
+            }
+            Ok(Poll::Ready(None)) => break,
+            Ok(Poll::Pending) => {
+                report.pending += 1;
+            }
+            Err(e) => {
+                report.errors.push(format!("Error in next: {}", e));
+                return report;
+            }
+        }
+    }
+    if let Some(text) = &args.query {
+        let (res, elapsed) = timed(|| plugin.query_inner(text));
+        match res {
+            Ok(entries) => {
+                report.query = Some(QueryReport {
+                    text: text.clone(),
+                    ms: millis(elapsed),
+                    entries: entries.iter().map(EntryReport::from).collect(),
+                })
+            }
+            Err(e) => report.errors.push(format!("Error in on_query: {}", e)),
+        }
+    }
+    report
+}
+
+fn timed<T, F: FnOnce() -> T>(func: F) -> (T, Duration) {
+    let started = Instant::now();
+    let retvl = func();
+    (retvl, started.elapsed())
+}
+
+fn millis(elapsed: Duration) -> f64 {
+    elapsed.as_secs_f64() * 1000.0
+}
+
+fn print_tree(report: &Report) {
+    println!("{}: started in {:.2} ms", report.plugin, report.start_ms);
+    for produced in &report.entries {
+        print_entry(&produced.entry, 1, Some(produced.ms));
+    }
+    println!(
+        "{} entries in {:.2} ms, yielding {} times without one",
+        report.entries.len(),
+        report.next_ms,
+        report.pending
+    );
+    if let Some(query) = &report.query {
+        println!(
+            "Query {:?}: {} entries in {:.2} ms",
+            query.text,
+            query.entries.len(),
+            query.ms
+        );
+        for ent in &query.entries {
+            print_entry(ent, 1, None);
+        }
+    }
+    for err in &report.errors {
+        println!("ERROR: {}", err);
+    }
+}
+
+fn print_entry(ent: &EntryReport, depth: usize, ms: Option<f64>) {
+    let indent = "  ".repeat(depth);
+    match ms {
+        Some(ms) => println!("{}- {} ({:.2} ms)", indent, ent.name, ms),
+        None => println!("{}- {}", indent, ent.name),
+    }
+    if !ent.exec.is_empty() {
+        println!("{}    exec: {}", indent, ent.exec.join(" "));
+    }
+    if !ent.flags.is_empty() {
+        println!("{}    flags: {}", indent, ent.flags.join(", "));
+    }
+    if !ent.search_terms.is_empty() {
+        println!(
+            "{}    search terms: {}",
+            indent,
+            ent.search_terms.join(", ")
+        );
+    }
+    for child in &ent.children {
+        print_entry(child, depth + 1, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::loadable::lua_config;
+
+    const PLUGIN: &str = r#"local count = 0
+plugin {
+    name = "Harness",
+    next = function()
+        count = count + 1
+        if count > 2 then
+            local typo = entry { nmae = "typo", exec = "true" }
+            return typo
+        end
+        return entry { name = "entry " .. count, exec = "echo " .. count }
+    end,
+    on_query = function(text)
+        return { entry { name = "echo " .. text, exec = "echo " .. text } }
+    end,
+}
+"#;
+
+    const ENDLESS: &str = r#"plugin {
+    name = "Endless",
+    next = function()
+        while true do
+            coroutine.yield()
+        end
+    end,
+}
+"#;
+
+    #[test]
+    fn entries_and_errors() {
+        let (_dir, conf) = lua_config(PLUGIN, "");
+        let file = conf.file;
+        let mut args = PluginTest {
+            file: file.clone(),
+            settings: None,
+            query: Some("hi".to_owned()),
+            limit: None,
+            timeout_ms: 10_000,
+            json: false,
+        };
+        let mut plugin = LuaPlugin::new(load_config(&args).unwrap()).unwrap();
+        let broken = collect(&mut plugin, &args);
+        args.limit = Some(2);
+        let mut plugin = LuaPlugin::new(load_config(&args).unwrap()).unwrap();
+        let limited = collect(&mut plugin, &args);
+        fs::write(&file, ENDLESS).unwrap();
+        args.limit = None;
+        args.timeout_ms = 100;
+        let mut plugin = LuaPlugin::new(load_config(&args).unwrap()).unwrap();
+        let endless = collect(&mut plugin, &args);
+
+        assert_eq!("Harness", broken.plugin);
+        let names: Vec<_> = broken.entries.iter().map(|p| &p.entry.name).collect();
+        assert_eq!(vec!["entry 1", "entry 2"], names);
+        assert_eq!(vec!["echo", "2"], broken.entries[1].entry.exec);
+        assert!(broken.query.is_none());
+        assert_eq!(1, broken.errors.len());
+        let location = format!("{}:7:", file.display());
+        assert!(
+            broken.errors[0].contains(&location),
+            "{:?} does not point at {}",
+            broken.errors[0],
+            location
+        );
+
+        assert!(limited.errors.is_empty());
+        assert_eq!(2, limited.entries.len());
+        assert_eq!("echo hi", limited.query.unwrap().entries[0].name);
+
+        assert!(endless.entries.is_empty());
+        assert!(endless.pending > 0);
+        assert_eq!(
+            vec!["Still producing entries after 100 ms; use --limit if that is expected"],
+            endless.errors
+        );
+    }
+}
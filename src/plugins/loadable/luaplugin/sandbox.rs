@@ -0,0 +1,429 @@
+//! Restricts what a plugin script can reach beyond building entries, and
+//! stops scripts that run away with the launcher.
+
+use super::super::Capability;
+use crate::utils::{expand_tilde, format_size, home_dir};
+
+use mlua::{Lua, StdLib};
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How many VM instructions run between checks of the limits.
+const HOOK_INTERVAL: u32 = 10_000;
+
+/// Replaces `io`, `os` and `require` with versions that only do what the
+/// plugin's capabilities allow. Runs with the sandboxed functions' originals
+/// as upvalues, which scripts cannot reach without the debug library.
+const PRELUDE: &str = r#"
+local allowed, check_path = ...
+local io_open, io_lines, io_popen = io.open, io.lines, io.popen
+local os_remove, os_rename = os.remove, os.rename
+local base_load, base_loadfile = load, loadfile
+
+-- Either would let the script switch off the watchdog's hook.
+debug = nil
+package.loaded.debug = nil
+jit = nil
+package.loaded.jit = nil
+
+io = {
+    write = io.write,
+    read = io.read,
+    stdout = io.stdout,
+    stderr = io.stderr,
+    type = io.type,
+    open = function(path, mode)
+        local err = check_path(path, mode or "r")
+        if err then
+            return nil, err
+        end
+        return io_open(path, mode)
+    end,
+    lines = function(path, ...)
+        if path ~= nil then
+            local err = check_path(path, "r")
+            if err then
+                error(err, 2)
+            end
+        end
+        return io_lines(path, ...)
+    end,
+    popen = allowed.exec and io_popen or nil,
+}
+
+os = {
+    clock = os.clock,
+    date = os.date,
+    difftime = os.difftime,
+    time = os.time,
+    getenv = allowed.env and os.getenv or nil,
+    execute = allowed.exec and os.execute or nil,
+    remove = function(path)
+        local err = check_path(path, "w")
+        if err then
+            return nil, err
+        end
+        return os_remove(path)
+    end,
+    rename = function(from, to)
+        local err = check_path(from, "w") or check_path(to, "w")
+        if err then
+            return nil, err
+        end
+        return os_rename(from, to)
+    end,
+}
+
+-- Precompiled chunks can break out of the VM, so only source is loaded.
+load = function(chunk, name, _, env)
+    return base_load(chunk, name, "t", env)
+end
+loadstring = function(chunk, name)
+    return base_load(chunk, name, "t")
+end
+loadfile = function(path)
+    local err = path and check_path(path, "r") or "Reading stdin is not allowed"
+    if err then
+        return nil, err
+    end
+    return base_loadfile(path, "t")
+end
+dofile = function(path)
+    return assert(loadfile(path))()
+end
+
+local fs = tmpas.fs
+for _, name in ipairs({ "read", "read_dir", "stat", "exists" }) do
+    local inner = fs[name]
+    fs[name] = function(path, ...)
+        local err = check_path(path, "r")
+        if err then
+            return nil, err
+        end
+        return inner(path, ...)
+    end
+end
+local glob = fs.glob
+fs.glob = function(pattern)
+    local err = check_path(pattern, "r")
+    if err then
+        return nil, err
+    end
+    local found, err = glob(pattern)
+    if not found then
+        return nil, err
+    end
+    local retvl = {}
+    for _, path in ipairs(found) do
+        if not check_path(path, "r") then
+            table.insert(retvl, path)
+        end
+    end
+    return retvl
+end
+if not allowed.exec then
+    tmpas.exec = nil
+end
+if not allowed.env then
+    tmpas.env = nil
+end
+
+package.loaded.io = io
+package.loaded.os = os
+if allowed.require then
+    -- Only Lua modules; a C module could do anything.
+    package.cpath = ""
+    package.loadlib = nil
+    package.loaders[3] = nil
+    package.loaders[4] = nil
+else
+    local modules = {}
+    for _, name in ipairs({ "coroutine", "table", "string", "math", "bit", "io", "os", "tmpas" }) do
+        modules[name] = package.loaded[name]
+    end
+    require = function(name)
+        local module = modules[name]
+        if module == nil then
+            error("Module " .. tostring(name) .. " cannot be loaded without \"require\" in the plugin's allow list", 2)
+        end
+        return module
+    end
+    package = nil
+end
+"#;
+
+/// Installs the watchdog's hook. The count hook only fires in the
+/// interpreter, so compiled traces are switched off for good. Everything
+/// that catches errors is wrapped to raise the watchdog's error again, so a
+/// script cannot carry on after it.
+const WATCHDOG: &str = r#"
+local check, interval = ...
+local base_pcall, base_xpcall, base_resume = pcall, xpcall, coroutine.resume
+jit.off()
+debug.sethook(function()
+    local problem = check()
+    if problem then
+        error(problem)
+    end
+end, "", interval)
+
+local function rethrow(ok, ...)
+    if not ok then
+        local problem = check()
+        if problem then
+            error(problem, 0)
+        end
+    end
+    return ok, ...
+end
+pcall = function(...)
+    return rethrow(base_pcall(...))
+end
+xpcall = function(...)
+    return rethrow(base_xpcall(...))
+end
+coroutine.resume = function(...)
+    return rethrow(base_resume(...))
+end
+local function resumed(ok, ...)
+    if not ok then
+        error((...), 0)
+    end
+    return ...
+end
+coroutine.wrap = function(func)
+    local thread = coroutine.create(func)
+    return function(...)
+        return resumed(coroutine.resume(thread, ...))
+    end
+end
+"#;
+
+/// A Lua state with the safe standard library plus the debug library, which
+/// `Watchdog::install` needs and `restrict` takes away again.
+pub fn new_state() -> Lua {
+    unsafe { Lua::unsafe_new_with(StdLib::ALL_SAFE | StdLib::DEBUG) }
+}
+
+/// Swaps the script's standard library for one limited to `allow`. Must run
+/// after `api::register`, since it restricts the `tmpas` module too, and
+/// after `Watchdog::install`, since it removes the debug library.
+pub fn restrict(env: &Lua, allow: &[Capability]) -> mlua::Result<()> {
+    let allowed = env.create_table()?;
+    for cap in allow {
+        allowed.raw_set(cap.name(), true)?;
+    }
+    let allow = allow.to_vec();
+    let check_path = env.create_function(move |_, (path, mode): (String, String)| {
+        Ok(check_path(&allow, &path, &mode))
+    })?;
+    env.load(PRELUDE)
+        .set_name("sandbox")?
+        .call::<_, ()>((allowed, check_path))
+}
+
+/// Returns why the script may not open `path` in `mode`, if it may not.
+fn check_path(allow: &[Capability], path: &str, mode: &str) -> Option<String> {
+    let write = mode.contains(['w', 'a', '+']);
+    if !write && allow.contains(&Capability::ReadAll) {
+        return None;
+    }
+    let in_home = home_dir()
+        .and_then(|home| home.canonicalize().ok())
+        .is_some_and(|home| {
+            resolve(Path::new(&expand_tilde(path))).is_some_and(|path| path.starts_with(home))
+        });
+    let needed = if write {
+        Capability::WriteHome
+    } else if in_home {
+        Capability::ReadHome
+    } else {
+        Capability::ReadAll
+    };
+    // Anything a plugin may write, it may read back.
+    let granted =
+        in_home && (allow.contains(&needed) || !write && allow.contains(&Capability::WriteHome));
+    if granted {
+        None
+    } else {
+        Some(format!(
+            "{}: Not allowed without \"{}\" in the plugin's allow list",
+            path,
+            needed.name()
+        ))
+    }
+}
+
+/// Makes a path absolute with every symlink resolved, even if the file
+/// itself does not exist yet.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    let path = env::current_dir().ok()?.join(path);
+    let mut existing = path.as_path();
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(mut retvl) = existing.canonicalize() {
+            retvl.extend(rest.iter().rev());
+            return Some(retvl);
+        }
+        // A `..` after a missing directory cannot be resolved.
+        rest.push(existing.file_name()?);
+        existing = existing.parent()?;
+    }
+}
+
+#[derive(Debug, Default)]
+struct WatchdogState {
+    deadline: Option<Instant>,
+    /// Why the watchdog stopped the script, once it has.
+    tripped: Option<String>,
+}
+
+/// Stops a script that runs for too long in one call or uses too much
+/// memory, by raising an error from an instruction count hook. Once that
+/// happens the plugin is considered dead, and the error is raised again
+/// wherever the script tries to catch it.
+///
+/// Memory is only checked as often as time, so a single large allocation
+/// can go over the limit before the script is stopped.
+///
+/// The hook is a Lua function rather than one set with `Lua::set_hook`,
+/// since LuaJIT cannot unwind an error raised by Rust code inside a hook.
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    state: Arc<Mutex<WatchdogState>>,
+    time_limit: Duration,
+}
+
+impl Watchdog {
+    pub fn install(env: &Lua, time_limit: Duration, memory_limit: usize) -> mlua::Result<Self> {
+        let retvl = Self {
+            state: Arc::default(),
+            time_limit,
+        };
+        let state = Arc::clone(&retvl.state);
+        let check = env.create_function(move |lua, ()| {
+            let mut state = state.lock().unwrap();
+            if state.tripped.is_some() {
+                return Ok(state.tripped.clone());
+            }
+            let problem = if state
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                format!("Plugin ran for longer than {:?}", time_limit)
+            } else if lua.used_memory() > memory_limit {
+                format!(
+                    "Plugin used more than {} of memory",
+                    format_size(memory_limit as u64)
+                )
+            } else {
+                return Ok(None);
+            };
+            state.tripped = Some(problem.clone());
+            Ok(Some(problem))
+        })?;
+        env.load(WATCHDOG)
+            .set_name("watchdog")?
+            .call::<_, ()>((check, HOOK_INTERVAL))?;
+        Ok(retvl)
+    }
+
+    /// Starts the clock on a call into the script. It stops when the
+    /// returned guard is dropped.
+    pub fn watch(&self) -> WatchGuard {
+        self.state.lock().unwrap().deadline = Some(Instant::now() + self.time_limit);
+        WatchGuard {
+            state: Arc::clone(&self.state),
+        }
+    }
+
+    pub fn tripped(&self) -> bool {
+        self.state.lock().unwrap().tripped.is_some()
+    }
+}
+
+pub struct WatchGuard {
+    state: Arc<Mutex<WatchdogState>>,
+}
+
+impl Drop for WatchGuard {
+    fn drop(&mut self) {
+        self.state.lock().unwrap().deadline = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities_and_limits() {
+        let env = new_state();
+        super::super::api::register(&env).unwrap();
+        let watchdog =
+            Watchdog::install(&env, Duration::from_millis(50), 64 * 1024 * 1024).unwrap();
+        restrict(&env, &[Capability::ReadHome]).unwrap();
+        let denied: (bool, bool, bool, bool, String) = env
+            .load(
+                r#"
+                local file, err = io.open("/etc/passwd")
+                local ok = pcall(require, "socket")
+                return os.execute == nil and debug == nil, tmpas.exec == nil, file == nil, ok, err
+            "#,
+            )
+            .eval()
+            .unwrap();
+        assert_eq!(
+            (
+                true,
+                true,
+                true,
+                false,
+                "/etc/passwd: Not allowed without \"read_all\" in the plugin's allow list"
+                    .to_owned()
+            ),
+            denied
+        );
+        assert_eq!(None, check_path(&[Capability::ReadHome], "~", "r"));
+        assert!(check_path(&[Capability::ReadHome], "~/../../etc/passwd", "r").is_some());
+        assert!(check_path(&[Capability::ReadHome], "~/notes.txt", "w").is_some());
+        assert_eq!(
+            None,
+            check_path(&[Capability::WriteHome], "~/new/notes.txt", "a")
+        );
+        assert_eq!(None, check_path(&[Capability::ReadAll], "/etc/passwd", "r"));
+
+        let runaway = env.load("while true do end");
+        let res = {
+            let _watch = watchdog.watch();
+            runaway.exec()
+        };
+        assert!(res.is_err());
+        assert!(watchdog.tripped());
+
+        let spin = "function() while true do end end";
+        for catcher in &[
+            format!("pcall({})", spin),
+            format!("xpcall({}, function(err) return err end)", spin),
+            format!("coroutine.resume(coroutine.create({}))", spin),
+            format!("pcall(coroutine.wrap({}))", spin),
+        ] {
+            let env = new_state();
+            super::super::api::register(&env).unwrap();
+            let watchdog =
+                Watchdog::install(&env, Duration::from_millis(50), 64 * 1024 * 1024).unwrap();
+            restrict(&env, &[]).unwrap();
+            let code = format!("while true do {} end", catcher);
+            let stubborn = env.load(&code);
+            let res = {
+                let _watch = watchdog.watch();
+                stubborn.exec()
+            };
+            assert!(res.is_err(), "{} caught the watchdog", catcher);
+            assert!(watchdog.tripped());
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::state::{RunResult, POLL_INTERVAL};
 use crate::{model::ListEntry, State};
 
 use smithay_client_toolkit as sctk;
@@ -47,11 +48,12 @@ pub enum KeyAction {
     Enter,
     Backspace,
     Escape,
+    Actions,
     Character(String),
 }
 
 impl KeyAction {
-    pub fn from_event(event: KbEvent) -> Option<Self> {
+    pub fn from_event(event: KbEvent, shift: bool) -> Option<Self> {
         let (keysym, buff) = match event {
             KbEvent::Key {
                 keysym,
@@ -64,7 +66,11 @@ impl KeyAction {
                 return None;
             }
         };
-        Self::from_keysym(keysym).or_else(|| buff.map(KeyAction::Character))
+        match Self::from_keysym(keysym) {
+            Some(KeyAction::Enter) if shift => Some(KeyAction::Actions),
+            Some(action) => Some(action),
+            None => buff.map(KeyAction::Character),
+        }
     }
     pub fn from_keysym(keysym: u32) -> Option<Self> {
         match keysym {
@@ -82,6 +88,9 @@ impl KeyAction {
                 Some(KeyAction::Backspace)
             }
             keysyms::XKB_KEY_Escape => Some(KeyAction::Escape),
+            keysyms::XKB_KEY_Tab | keysyms::XKB_KEY_KP_Tab | keysyms::XKB_KEY_ISO_Left_Tab => {
+                Some(KeyAction::Actions)
+            }
             _ => None,
         }
     }
@@ -90,6 +99,7 @@ impl KeyAction {
 pub struct EventStore {
     window_event: Option<WEvent>,
     key_events: Vec<KeyAction>,
+    shift: bool,
 }
 
 impl EventStore {
@@ -97,12 +107,20 @@ impl EventStore {
         Self {
             window_event: None,
             key_events: Vec::with_capacity(16),
+            shift: false,
+        }
+    }
+    pub fn push_key_event(&mut self, event: KbEvent) {
+        if let KbEvent::Modifiers { modifiers } = event {
+            self.shift = modifiers.shift;
+        } else if let Some(act) = KeyAction::from_event(event, self.shift) {
+            self.key_events.push(act);
         }
     }
 }
 pub fn run(state: State) {
     if let Some((state, to_run)) = run_inner(state) {
-        state.run(&to_run);
+        state.exec(&to_run);
     }
 }
 
@@ -148,10 +166,8 @@ fn run_inner(mut state: State) -> Option<(State, ListEntry)> {
                 None,
                 RepeatKind::System,
                 move |event, _, mut dd| {
-                    if let Some(act) = KeyAction::from_event(event) {
-                        let store = dd.get::<EventStore>().unwrap();
-                        store.key_events.push(act);
-                    }
+                    let store = dd.get::<EventStore>().unwrap();
+                    store.push_key_event(event);
                 },
             ) {
                 Ok((kbd, repeat_source)) => {
@@ -188,10 +204,8 @@ fn run_inner(mut state: State) -> Option<(State, ListEntry)> {
                 None,
                 RepeatKind::System,
                 move |event, _, mut dd| {
-                    if let Some(act) = KeyAction::from_event(event) {
-                        let store = dd.get::<EventStore>().unwrap();
-                        store.key_events.push(act);
-                    }
+                    let store = dd.get::<EventStore>().unwrap();
+                    store.push_key_event(event);
                 },
             ) {
                 Ok((kbd, repeat_source)) => {
@@ -249,7 +263,8 @@ fn run_inner(mut state: State) -> Option<(State, ListEntry)> {
     if !env.get_shell().unwrap().needs_configure() {
         // initial draw to bootstrap on wl_shell
         if let Some(pool) = pools.pool() {
-            redraw(&mut bar, &mut resl, pool, window.surface(), dimensions).expect("Failed to draw")
+            redraw(&mut bar, &mut resl, None, pool, window.surface(), dimensions)
+                .expect("Failed to draw")
         }
         window.refresh();
     }
@@ -262,16 +277,54 @@ fn run_inner(mut state: State) -> Option<(State, ListEntry)> {
 
     let mut needs_redraw = false;
     let mut can_expand = true;
+    let mut status: Option<String> = None;
+    let mut showing_actions = false;
     loop {
         let mut had_handled = false;
         let old_buffer = bar.buffer.clone();
         for action in next_action.key_events.drain(..) {
             if action == KeyAction::Enter {
                 if let Some(selected) = resl.selected().cloned() {
-                    return Some((state, selected));
+                    match state.run(&selected) {
+                        RunResult::Nothing => {
+                            continue;
+                        }
+                        RunResult::Status(msg) => {
+                            status = Some(msg);
+                            needs_redraw = true;
+                            continue;
+                        }
+                        RunResult::Exec(ent) => {
+                            return Some((state, ent));
+                        }
+                        RunResult::Entries(entries) => {
+                            resl.set_results(entries);
+                            status = Some(format!("{} (Esc to go back)", selected.name()));
+                            showing_actions = true;
+                            can_expand = false;
+                            needs_redraw = true;
+                            continue;
+                        }
+                    }
                 }
             }
-            else if action == KeyAction::Escape {
+            else if action == KeyAction::Actions {
+                if let Some(selected) = resl.selected().cloned() {
+                    resl.set_results(state.actions(&selected));
+                    status = Some(format!("Actions for {} (Esc to go back)", selected.name()));
+                    showing_actions = true;
+                    can_expand = false;
+                    needs_redraw = true;
+                }
+                continue;
+            } else if action == KeyAction::Escape && showing_actions {
+                resl.set_results(state.search(&bar.buffer, 4 * resl.max_entries()));
+                status = None;
+                showing_actions = false;
+                can_expand = true;
+                needs_redraw = true;
+                continue;
+            } else if action == KeyAction::Escape {
                 return None;
             }
             let action = match bar.push_action(action) {
@@ -299,7 +352,14 @@ fn run_inner(mut state: State) -> Option<(State, ListEntry)> {
                 ActionResponse::Continue(action) => action,
             };
         }
+        let changed = state.poll();
         if old_buffer != bar.buffer {
+            status = None;
+            showing_actions = false;
+            resl.set_results(state.search(&bar.buffer, 4 * resl.max_entries()));
+            needs_redraw = true;
+            can_expand = true;
+        } else if changed && !showing_actions {
             resl.set_results(state.search(&bar.buffer, 4 * resl.max_entries()));
             needs_redraw = true;
             can_expand = true;
@@ -310,6 +370,10 @@ fn run_inner(mut state: State) -> Option<(State, ListEntry)> {
             can_expand = resl.cur_results_height() >= target_height;
             needs_redraw = true;
         }
+        if let Some(warning) = state.take_warning() {
+            status = Some(warning);
+            needs_redraw = true;
+        }
         if had_handled {
             eprintln!(
                 "Finished key events. Buffer: {:?}, {:?}",
@@ -338,20 +402,30 @@ fn run_inner(mut state: State) -> Option<(State, ListEntry)> {
             window.refresh();
             if let Some(pool) = pools.pool() {
                 eprintln!("Doing redraw.");
-                redraw(&mut bar, &mut resl, pool, window.surface(), dimensions)
-                    .expect("Failed to draw");
+                redraw(
+                    &mut bar,
+                    &mut resl,
+                    status.as_deref(),
+                    pool,
+                    window.surface(),
+                    dimensions,
+                )
+                .expect("Failed to draw");
                 needs_redraw = false;
             }
         }
 
         // always flush the connection before going to sleep waiting for events
         display.flush().unwrap();
-        event_loop.dispatch(None, &mut next_action).unwrap();
+        event_loop
+            .dispatch(Some(POLL_INTERVAL), &mut next_action)
+            .unwrap();
     }
 }
 fn redraw(
     obj: &mut SearchBar,
     resl: &mut resultslist::EntryList,
+    status: Option<&str>,
     pool: &mut MemPool,
     surface: &wl_surface::WlSurface,
     (buf_x, buf_y): (u32, u32),
@@ -377,7 +451,12 @@ fn redraw(
         &mut canvas,
     );
     let list_y = obj.config.outer_height() + obj.config.padding;
-    let list_height = canvas.height - list_y;
+    let mut list_height = canvas.height - list_y;
+    if let Some(status) = status {
+        let text = obj.config.status_text(canvas.height, status);
+        list_height = list_height.saturating_sub(obj.config.status_height());
+        canvas.draw(&text);
+    }
     resl.display(
         Rect {
             x: 0,
@@ -52,6 +52,8 @@ pub struct SearchbarConfig {
     pub buffer_size: f32,
     pub buffer_inner_padding: usize,
 
+    pub status_size: f32,
+
     pub padding: usize,
     pub spacing: usize,
 
@@ -101,6 +103,7 @@ impl Default for SearchbarConfig {
             buffer_font: FontConfig::default(),
             buffer_size: 32.0,
             buffer_inner_padding: 1,
+            status_size: 20.0,
             padding: 8,
             spacing: 16,
             colors: SearchbarColorConfig {
@@ -210,6 +213,21 @@ impl SearchbarConfig {
             buffer,
         )
     }
+    pub fn status_text<'a>(&'a self, canvas_height: usize, status: &'a str) -> Text<'a> {
+        let x = self.padding;
+        let y = canvas_height.saturating_sub(self.status_height());
+        Text::new(
+            (x, y),
+            self.colors.label.fg,
+            self.buffer_font.get_font().unwrap(),
+            self.status_size,
+            1.0,
+            status,
+        )
+    }
+    pub fn status_height(&self) -> usize {
+        self.status_size.ceil() as usize + self.padding
+    }
     pub const fn outer_height(&self) -> usize {
         self.inner_height() + 2 * self.padding
     }
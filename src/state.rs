@@ -1,28 +1,125 @@
-use crate::model::{EntryPath, EntryPlugin, ListEntry};
+use crate::config::ConfigSource;
+use crate::model::{EntryPath, ListEntry, Selected};
+use crate::plugins::{BuiltinPlugins, LoadablePlugins};
+use crate::supervisor::{PluginBudget, Supervisor};
+use crate::utils::{find_in_path, shell_quote};
+use crate::watcher::{normalize, FileWatcher};
 use crate::{config::Config, model::entry_tree_with_paths};
 
-use nix::unistd::{execvp, fork, ForkResult};
+use nix::errno::Errno;
+use nix::fcntl::{open, OFlag};
+use nix::sys::stat::Mode;
+use nix::sys::wait::waitpid;
+use nix::unistd::{close, dup2, execvp, fork, pipe2, read, setsid, write, ForkResult};
 use std::collections::{HashMap, HashSet};
 use std::ffi::CString;
 use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+/// How often the UIs call `State::poll` while idle.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long `State::poll` spends loading entries at most.
+const POLL_SLICE: Duration = Duration::from_millis(20);
 
 pub struct State {
     pub config: Config,
     entries: Vec<ListEntry>,
     entries_by_cmd: HashMap<Vec<String>, Vec<DedupMetadata>>,
-    plugins: Vec<Box<dyn EntryPlugin>>,
+    plugins: Vec<PluginSlot>,
+    next_plugin_id: usize,
+    /// Every entry as its plugin produced it, tagged with the plugin's id, so
+    /// that the entries can be rebuilt without those of a reloaded plugin.
+    produced: Vec<(usize, ListEntry)>,
     delete_queue: Vec<EntryPath>,
+    query_cache: Option<(String, Vec<ListEntry>)>,
+    /// An entry that needs confirming and has been selected once already.
+    /// Searching for something else or opening an actions menu in between
+    /// cancels it.
+    awaiting_confirm: Option<ListEntry>,
+    /// The text of the latest search.
+    last_search: String,
+    /// Where the config came from, once it is being watched for changes.
+    source: Option<ConfigSource>,
+    watcher: Option<FileWatcher>,
+    /// Problems with reloading, for the UI to show.
+    warnings: Vec<String>,
+    /// Set while some plugins still have entries for `poll` to load, like
+    /// ones that were reloaded and are starting in the background, or ones
+    /// that had nothing ready the last time they were asked.
+    loading: bool,
 }
 
-fn matches_search(key: &str, ent: &ListEntry) -> bool {
+/// A running plugin and the part of the config it was created from.
+struct PluginSlot {
+    id: usize,
+    source: PluginSource,
+    plugin: Supervisor,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PluginSource {
+    Builtin(BuiltinPlugins),
+    Loaded(LoadablePlugins),
+}
+
+impl PluginSource {
+    fn spawn(&self, budget: PluginBudget) -> Supervisor {
+        match self.clone() {
+            PluginSource::Builtin(builtin) => {
+                Supervisor::spawn(format!("{:?}", builtin), budget, move || builtin.load())
+            }
+            PluginSource::Loaded(loaded) => {
+                Supervisor::spawn(loaded.label(), budget, move || loaded.load())
+            }
+        }
+    }
+
+    /// The file the plugin is loaded from, if any.
+    fn file(&self) -> Option<&Path> {
+        match self {
+            PluginSource::Loaded(LoadablePlugins::Lua(conf)) => Some(&conf.file),
+            _ => None,
+        }
+    }
+}
+
+/// The config with only what builtin plugins may read in `start` left in, to
+/// tell whether they need restarting after a reload.
+fn builtin_settings(config: &Config) -> Config {
+    Config {
+        builtin_plugins: Vec::new(),
+        loaded_plugins: Vec::new(),
+        interfaces: HashMap::new(),
+        ..config.clone()
+    }
+}
+
+/// How well an entry matches the search key, lower being better, or `None` if
+/// it does not match at all. Expects `key` to already be lowercase.
+fn search_score(key: &str, ent: &ListEntry) -> Option<u8> {
     if key.is_empty() {
-        return true;
+        return Some(0);
     }
-    ent.name().to_lowercase().contains(key)
-        || ent
-            .search_terms
-            .iter()
-            .any(|term| term.to_lowercase().contains(key))
+    let name = ent.name().to_lowercase();
+    let mut terms = ent.search_terms.iter().map(|term| term.to_lowercase());
+    if name == key || terms.clone().any(|term| term == key) {
+        Some(0)
+    } else if name.starts_with(key) {
+        Some(1)
+    } else if name.contains(key) {
+        Some(2)
+    } else if terms.any(|term| term.contains(key)) {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+fn matches_search(key: &str, ent: &ListEntry) -> bool {
+    search_score(key, ent).is_some()
 }
 
 impl State {
@@ -31,52 +128,258 @@ impl State {
             config,
             entries: Default::default(),
             plugins: Default::default(),
+            next_plugin_id: 0,
+            produced: Default::default(),
             entries_by_cmd: Default::default(),
             delete_queue: Default::default(),
+            query_cache: None,
+            awaiting_confirm: None,
+            last_search: String::new(),
+            source: None,
+            watcher: None,
+            warnings: Default::default(),
+            loading: false,
         }
     }
     pub fn start(&mut self) {
-        for builtin in &self.config.builtin_plugins {
-            self.plugins.push(builtin.load());
+        self.sync_plugins(false, &HashSet::new());
+        for slot in &mut self.plugins {
+            slot.plugin.finish_start();
         }
-        for loaded in &self.config.loaded_plugins {
-            self.plugins.push(loaded.load());
+        loop {
+            match self.load_next_entry() {
+                Poll::Ready(Some(())) => {}
+                Poll::Ready(None) => break,
+                Poll::Pending => {
+                    self.loading = true;
+                    break;
+                }
+            }
         }
-        for plugin in &mut self.plugins {
-            plugin.start(&self.config);
+        self.delete_queued();
+    }
+
+    /// Applies changes to the watched files and loads the entries plugins
+    /// have finished in the meantime, without waiting on any plugin. Returns
+    /// whether the entries changed, so that the UI can search again.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = self.reload_changed();
+        let started = Instant::now();
+        while self.loading && started.elapsed() < POLL_SLICE {
+            match self.load_next_entry() {
+                Poll::Ready(Some(())) => {
+                    changed = true;
+                }
+                Poll::Ready(None) => {
+                    self.loading = false;
+                }
+                Poll::Pending => {}
+            }
         }
-        while let Some(()) = self.load_next_entry() {}
         self.delete_queued();
+        if changed {
+            self.query_cache = None;
+        }
+        changed
     }
-    fn search_loaded(&mut self, key: &str, max_height: usize) -> Vec<ListEntry> {
-        let mut retvl = Vec::new();
-        let mut height = 0;
-        let key = key.to_lowercase();
-        for ent in self.entries.iter() {
-            if matches_search(&key, ent) {
-                retvl.push(ent.clone());
-                height += entry_tree_with_paths(std::slice::from_ref(ent), 1024).count();
-                if height >= max_height {
-                    return retvl;
+
+    /// Reloads the config from `source` whenever its file changes, and Lua
+    /// plugins whenever their scripts change.
+    pub fn watch(&mut self, source: ConfigSource) {
+        match FileWatcher::new() {
+            Ok(watcher) => {
+                self.watcher = Some(watcher);
+            }
+            Err(e) => {
+                eprintln!("WARNING: Cannot watch the config for changes: {}", e);
+                return;
+            }
+        }
+        self.source = Some(source);
+        self.update_watches();
+    }
+
+    fn update_watches(&mut self) {
+        let config_file = self.source.as_ref().and_then(|source| source.path.clone());
+        let plugin_files = self.plugins.iter().filter_map(|slot| slot.source.file());
+        if let Some(watcher) = self.watcher.as_mut() {
+            watcher.watch(config_file.into_iter().chain(plugin_files.map(Path::to_owned)));
+        }
+    }
+
+    /// Applies whatever changed in the watched files since the last call,
+    /// re-creating only the plugins affected. The new plugins start in the
+    /// background and their entries are loaded by `poll`. Returns whether
+    /// anything was reloaded.
+    fn reload_changed(&mut self) -> bool {
+        let changed = match self.watcher.as_mut() {
+            Some(watcher) => watcher.changed(),
+            None => {
+                return false;
+            }
+        };
+        if changed.is_empty() {
+            return false;
+        }
+        eprintln!("Reloading after changes to {:?}", changed);
+        let mut builtins_stale = false;
+        let source = self.source.clone().unwrap_or_default();
+        let config_file = source.path.as_deref().map(normalize);
+        if config_file.is_some_and(|file| changed.contains(&file)) {
+            match source.load() {
+                Some(config) => {
+                    builtins_stale = builtin_settings(&config) != builtin_settings(&self.config);
+                    self.config = config;
+                }
+                None => {
+                    self.warnings
+                        .push("Could not reload the config; keeping the old one".to_owned());
+                }
+            }
+        }
+        self.sync_plugins(builtins_stale, &changed);
+        self.update_watches();
+        self.loading = true;
+        true
+    }
+
+    /// Brings the running plugins in line with the config. Plugins that are
+    /// new, or stale because of a change, are created and asked to start;
+    /// ones that are no longer configured are dropped along with their
+    /// entries; the rest keep running untouched.
+    fn sync_plugins(&mut self, builtins_stale: bool, changed_files: &HashSet<PathBuf>) {
+        let budget = self.config.plugin_budget;
+        let builtins = self.config.builtin_plugins.iter().copied();
+        let loaded = self.config.loaded_plugins.iter().cloned();
+        let sources: Vec<_> = builtins
+            .map(PluginSource::Builtin)
+            .chain(loaded.map(PluginSource::Loaded))
+            .collect();
+        let mut old = std::mem::take(&mut self.plugins);
+        for source in sources {
+            let stale = match &source {
+                PluginSource::Builtin(_) => builtins_stale,
+                PluginSource::Loaded(_) => source
+                    .file()
+                    .is_some_and(|file| changed_files.contains(&normalize(file))),
+            };
+            let reused = old
+                .iter()
+                .position(|slot| slot.source == source)
+                .filter(|_| !stale);
+            let slot = match reused {
+                Some(idx) => old.remove(idx),
+                None => {
+                    let mut plugin = source.spawn(budget);
+                    plugin.begin_start(&self.config);
+                    self.next_plugin_id += 1;
+                    PluginSlot {
+                        id: self.next_plugin_id,
+                        source,
+                        plugin,
+                    }
                 }
+            };
+            self.plugins.push(slot);
+        }
+        if !old.is_empty() {
+            self.rebuild_entries();
+        }
+    }
+
+    /// Rebuilds the entries from what the plugins still running produced,
+    /// dropping everything else.
+    fn rebuild_entries(&mut self) {
+        let live: HashSet<_> = self.plugins.iter().map(|slot| slot.id).collect();
+        let mut produced = std::mem::take(&mut self.produced);
+        produced.retain(|(id, _)| live.contains(id));
+        self.entries.clear();
+        self.entries_by_cmd.clear();
+        self.delete_queue.clear();
+        for (_, ent) in &produced {
+            self.insert_entry(ent.clone());
+        }
+        self.produced = produced;
+        self.delete_queued();
+        self.query_cache = None;
+        self.awaiting_confirm = None;
+    }
+    /// Why any plugins have been disabled since the last call, for the UI to
+    /// show as a status.
+    pub fn take_warning(&mut self) -> Option<String> {
+        let mut warnings = std::mem::take(&mut self.warnings);
+        let disabled = self.plugins.iter_mut();
+        warnings.extend(disabled.filter_map(|slot| slot.plugin.take_warning()));
+        if warnings.is_empty() {
+            None
+        } else {
+            Some(warnings.join("; "))
+        }
+    }
+
+    fn search_loaded(&mut self, text: &str, max_height: usize) -> Vec<ListEntry> {
+        let dynamic = self.query_plugins(text);
+        let key = text.to_lowercase();
+
+        // Entries generated for this query always show up, but ones that do
+        // not match the text itself rank below everything that does.
+        let mut ranked: Vec<(u8, &ListEntry)> = dynamic
+            .iter()
+            .map(|ent| (search_score(&key, ent).unwrap_or(u8::MAX), ent))
+            .collect();
+        for ent in self.entries.iter() {
+            if let Some(score) = search_score(&key, ent) {
+                ranked.push((score, ent));
             } else {
-                for child in ent
-                    .children
-                    .iter()
-                    .filter(|child| matches_search(&key, child))
-                {
-                    retvl.push(child.clone());
-                    height += entry_tree_with_paths(std::slice::from_ref(child), 1024).count();
-                    if height >= max_height {
-                        return retvl;
+                for child in ent.children.iter() {
+                    if let Some(score) = search_score(&key, child) {
+                        ranked.push((score, child));
                     }
                 }
             }
         }
+        ranked.sort_by_key(|(score, _)| *score);
 
+        let mut retvl = Vec::new();
+        let mut height = 0;
+        for (_, ent) in ranked {
+            retvl.push(ent.clone());
+            height += entry_tree_with_paths(std::slice::from_ref(ent), 1024).count();
+            if height >= max_height {
+                break;
+            }
+        }
+        if retvl.is_empty() && !text.trim().is_empty() {
+            for slot in &mut self.plugins {
+                retvl.extend(slot.plugin.fallback(text));
+            }
+        }
         retvl
     }
 
+    /// The entries every plugin made for `key`. Those of plugins that allow
+    /// it are reused while the key stays the same; the rest are asked anew.
+    fn query_plugins(&mut self, key: &str) -> Vec<ListEntry> {
+        let hit = matches!(&self.query_cache, Some((cached_key, _)) if cached_key == key);
+        let mut cached = Vec::new();
+        let mut fresh = Vec::new();
+        if !key.is_empty() {
+            for slot in &mut self.plugins {
+                if !slot.plugin.caches_queries() {
+                    fresh.extend(slot.plugin.query(key));
+                } else if !hit {
+                    cached.extend(slot.plugin.query(key));
+                }
+            }
+        }
+        match &self.query_cache {
+            Some((_, entries)) if hit => cached = entries.clone(),
+            _ => self.query_cache = Some((key.to_owned(), cached.clone())),
+        }
+        cached.extend(fresh);
+        cached
+    }
+
     fn cur_search_height(&self, key: &str) -> usize {
         let mut retvl = 0;
         let key = key.to_lowercase();
@@ -98,12 +401,26 @@ impl State {
 
     pub fn search(&mut self, key: &str, max_height: usize) -> Vec<ListEntry> {
         const BATCH_SIZE: usize = 30;
+        // Searching again for the same text only loads more results.
+        if key != self.last_search {
+            self.awaiting_confirm = None;
+            self.last_search = key.to_owned();
+        }
         let mut finished_loading = false;
         loop {
             for _ in 0..BATCH_SIZE {
-                if self.load_next_entry().is_none() {
-                    finished_loading = true;
-                    break;
+                match self.load_next_entry() {
+                    Poll::Ready(Some(())) => {}
+                    Poll::Ready(None) => {
+                        finished_loading = true;
+                        break;
+                    }
+                    // Plugins still working on entries are left to `poll`.
+                    Poll::Pending => {
+                        self.loading = true;
+                        finished_loading = true;
+                        break;
+                    }
                 }
             }
             self.delete_queued();
@@ -113,18 +430,47 @@ impl State {
         }
     }
 
-    fn load_next_entry(&mut self) -> Option<()> {
-        let ent = self.plugins.iter_mut().find_map(|plugin| plugin.next());
-        let ent = match ent {
+    /// Loads an entry from the first plugin that has one ready. Returns
+    /// `Poll::Pending` if none had but some are still working on more.
+    fn load_next_entry(&mut self) -> Poll<Option<()>> {
+        let mut pending = false;
+        let mut next = None;
+        for slot in self.plugins.iter_mut() {
+            match slot.plugin.next() {
+                Poll::Ready(Some(ent)) => {
+                    next = Some((slot.id, ent));
+                    break;
+                }
+                Poll::Ready(None) => {}
+                Poll::Pending => {
+                    pending = true;
+                }
+            }
+        }
+        let (id, ent) = match next {
             Some(n) => n,
+            None if pending => {
+                return Poll::Pending;
+            }
             None => {
-                return None;
+                return Poll::Ready(None);
             }
         };
+        self.produced.push((id, ent.clone()));
+        self.insert_entry(ent);
+        Poll::Ready(Some(()))
+    }
+
+    fn insert_entry(&mut self, ent: ListEntry) {
         let root_path = EntryPath::new().then(self.entries.len());
         let tmp = [ent];
         for (path, child) in entry_tree_with_paths(&tmp, 1024) {
             let path = root_path + path.tail_from(1);
+            // Entries with callbacks can look alike and still do different
+            // things, so they are never merged.
+            if child.tag.is_some() {
+                continue;
+            }
             let cmd = child.exec_command.clone();
             let cur_dups = self.entries_by_cmd.entry(cmd).or_default();
             let meta = DedupMetadata::new(path, child);
@@ -151,7 +497,6 @@ impl State {
         }
         let [ent] = tmp;
         self.entries.push(ent);
-        Some(())
     }
     fn delete_queued(&mut self) {
         self.delete_queue
@@ -180,18 +525,192 @@ impl State {
         }
     }
 
-    #[allow(dead_code)]
-    pub fn run(&self, ent: &ListEntry) {
-        let binary: &str = match ent.exec_name() {
-            Some(n) => n,
+    /// Runs the given entry.
+    ///
+    /// Entries that keep the launcher open are spawned in the background and a
+    /// status message is returned for the UI to display; everything else is
+    /// handed back as `RunResult::Exec` so that the UI can tear itself down
+    /// before calling `State::exec`.
+    ///
+    /// Entries flagged as plugin handled are first offered to the plugins; if
+    /// none claims them their command is run as usual. Entries that need
+    /// confirming only run when selected twice in a row.
+    pub fn run(&mut self, ent: &ListEntry) -> RunResult {
+        if !ent.is_runnable() {
+            return RunResult::Nothing;
+        }
+        let confirmed = self.awaiting_confirm.take().as_ref() == Some(ent);
+        if ent.exec_flags.needs_confirm() && !confirmed {
+            self.awaiting_confirm = Some(ent.clone());
+            return RunResult::Status(format!("Select {} again to confirm", ent.name()));
+        }
+        if ent.exec_flags.plugin_handled() {
+            let selected = self
+                .plugins
+                .iter_mut()
+                .find_map(|slot| slot.plugin.select(ent));
+            match selected {
+                Some(Selected::Done(msg)) => {
+                    // Whatever the plugin did may change its query results.
+                    self.query_cache = None;
+                    return RunResult::Status(msg);
+                }
+                Some(Selected::Run(next)) => {
+                    return self.run(&next);
+                }
+                Some(Selected::Entries(entries)) => {
+                    return RunResult::Entries(entries);
+                }
+                None => {}
+            }
+        }
+        if !ent.has_command() {
+            return RunResult::Nothing;
+        }
+        if !self.config.keep_open && !ent.exec_flags.keep_open() {
+            return RunResult::Exec(ent.clone());
+        }
+        match self.spawn(ent) {
+            Ok(()) => RunResult::Status(format!("Launched {}", ent.name())),
+            Err(e) => RunResult::Status(format!("Failed to launch {}: {}", ent.name(), e)),
+        }
+    }
+
+    /// Builds the actions menu for an entry: the built-in actions followed by
+    /// whatever the plugins contribute.
+    pub fn actions(&mut self, ent: &ListEntry) -> Vec<ListEntry> {
+        self.awaiting_confirm = None;
+        let mut retvl = Vec::new();
+        if ent.has_command() {
+            retvl.push(ListEntry {
+                display_name: Some("Run in terminal".to_owned()),
+                exec_command: ent.exec_command.clone(),
+                exec_flags: ent.exec_flags.with_term(true),
+                ..Default::default()
+            });
+            retvl.push(ListEntry {
+                display_name: Some("Copy command".to_owned()),
+                exec_command: vec![
+                    "wl-copy".to_owned(),
+                    "--".to_owned(),
+                    shell_quote(&ent.exec_command),
+                ],
+                ..Default::default()
+            });
+        }
+        let folder = ent
+            .source
+            .clone()
+            .or_else(|| ent.exec_command.first().and_then(|cmd| find_in_path(cmd)))
+            .and_then(|path| path.parent().map(|p| p.to_owned()));
+        if let Some(folder) = folder {
+            retvl.push(ListEntry {
+                display_name: Some("Open containing folder".to_owned()),
+                exec_command: vec!["xdg-open".to_owned(), folder.display().to_string()],
+                ..Default::default()
+            });
+        }
+        retvl.push(metadata_entry(ent));
+        for slot in &mut self.plugins {
+            retvl.extend(slot.plugin.actions(ent));
+        }
+        retvl
+    }
+
+    /// Replaces the current process with the entry's command, or forks first
+    /// if the entry asks for it.
+    pub fn exec(&self, ent: &ListEntry) {
+        let (fname, argv) = match self.make_argv(ent) {
+            Some(res) => res,
             None => {
                 return;
             }
         };
-        let (fname, argv) = if ent.exec_flags.is_term() {
-            let raw = self.config.make_terminal_command(&ent);
-            let argv: Vec<_> = raw
-                .split(' ')
+        if ent.exec_flags.should_fork() {
+            let fork_res = unsafe { fork() };
+            match fork_res {
+                Ok(ForkResult::Parent { .. }) => {
+                    return;
+                }
+                Ok(ForkResult::Child) => {}
+                Err(e) => {
+                    panic!("Failed to fork: {:?}", e);
+                }
+            }
+        }
+        execvp(&fname, &argv).unwrap();
+    }
+
+    fn spawn(&self, ent: &ListEntry) -> nix::Result<()> {
+        let (fname, argv) = match self.make_argv(ent) {
+            Some(res) => res,
+            None => {
+                return Ok(());
+            }
+        };
+        // If the program cannot be started, its errno comes back through
+        // the pipe; a successful exec closes the pipe without a word.
+        let (reader, writer) = pipe2(OFlag::O_CLOEXEC)?;
+        let child = match unsafe { fork() } {
+            Ok(ForkResult::Parent { child }) => child,
+            Ok(ForkResult::Child) => {
+                // Double fork so that the launched process gets reparented to init
+                // and never lingers as a zombie of the launcher.
+                let _ = close(reader);
+                let _ = setsid();
+                let err = match unsafe { fork() } {
+                    Ok(ForkResult::Child) => {
+                        if let Ok(null) = open("/dev/null", OFlag::O_RDWR, Mode::empty()) {
+                            for fd in 0..3 {
+                                let _ = dup2(null, fd);
+                            }
+                        }
+                        match execvp(&fname, &argv) {
+                            Ok(never) => match never {},
+                            Err(e) => e,
+                        }
+                    }
+                    Ok(ForkResult::Parent { .. }) => unsafe { nix::libc::_exit(0) },
+                    Err(e) => e,
+                };
+                let errno = err.as_errno().unwrap_or(Errno::UnknownErrno) as i32;
+                let _ = write(writer, &errno.to_ne_bytes());
+                unsafe { nix::libc::_exit(1) }
+            }
+            Err(e) => {
+                let _ = close(reader);
+                let _ = close(writer);
+                return Err(e);
+            }
+        };
+        let _ = close(writer);
+        let waited = waitpid(child, None);
+        let mut buf = [0; 4];
+        let mut len = 0;
+        while len < buf.len() {
+            match read(reader, &mut buf[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(nix::Error::Sys(Errno::EINTR)) => {}
+                Err(_) => break,
+            }
+        }
+        let _ = close(reader);
+        waited?;
+        if len == buf.len() {
+            Err(nix::Error::Sys(Errno::from_i32(i32::from_ne_bytes(buf))))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn make_argv(&self, ent: &ListEntry) -> Option<(CString, Vec<CString>)> {
+        let binary: &str = ent.exec_name()?;
+        let res = if ent.exec_flags.is_term() {
+            let argv: Vec<_> = self
+                .config
+                .make_terminal_argv(&ent)
+                .into_iter()
                 .map(|part| CString::new(part).unwrap())
                 .collect();
             let fname = argv.first().cloned().unwrap();
@@ -206,22 +725,47 @@ impl State {
                 .collect();
             (binary, argv)
         };
-        if ent.exec_flags.should_fork() {
-            let fork_res = unsafe { fork() };
-            match fork_res {
-                Ok(ForkResult::Parent { .. }) => {
-                    return;
-                }
-                Ok(ForkResult::Child) => {}
-                Err(e) => {
-                    panic!("Failed to fork: {:?}", e);
-                }
-            }
-        }
-        execvp(&fname, &argv).unwrap();
+        Some(res)
     }
 }
 
+fn metadata_entry(ent: &ListEntry) -> ListEntry {
+    let info = |label: &str, value: String| ListEntry {
+        display_name: Some(format!("{}: {}", label, value)),
+        ..Default::default()
+    };
+    let mut children = vec![
+        info("Name", ent.name().to_owned()),
+        info("Command", ent.exec_command.join(" ")),
+        info("Terminal", ent.exec_flags.is_term().to_string()),
+    ];
+    if !ent.search_terms.is_empty() {
+        children.push(info("Search terms", ent.search_terms.join(", ")));
+    }
+    if let Some(source) = ent.source.as_ref() {
+        children.push(info("Source", source.display().to_string()));
+    }
+    ListEntry {
+        display_name: Some("Show metadata".to_owned()),
+        children,
+        ..Default::default()
+    }
+}
+
+/// What a UI should do after asking the `State` to run an entry.
+#[derive(Debug, Clone)]
+pub enum RunResult {
+    /// The entry had nothing to run.
+    Nothing,
+    /// The launcher stays open and should show this message to the user.
+    Status(String),
+    /// The launcher should close and then call `State::exec` on this entry.
+    Exec(ListEntry),
+    /// The launcher stays open and shows these entries in place of the
+    /// results, like the actions menu.
+    Entries(Vec<ListEntry>),
+}
+
 #[derive(Debug, PartialEq, Eq)]
 struct DedupMetadata {
     path: EntryPath,
@@ -316,3 +860,127 @@ impl SetCmp {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::model::RunFlags;
+    #[cfg(feature = "plugin-lua")]
+    use crate::plugins::lua_config;
+
+    #[test]
+    fn spawn_reports_failures() {
+        let state = State::new(Config::default());
+        let entry = |cmd: &str| ListEntry {
+            exec_command: vec![cmd.to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(Ok(()), state.spawn(&entry("true")));
+        assert_eq!(
+            Err(nix::Error::Sys(Errno::ENOENT)),
+            state.spawn(&entry("tmpas-no-such-program"))
+        );
+    }
+
+    #[test]
+    fn confirm_needs_consecutive_selections() {
+        let mut state = State::new(Config::default());
+        let reboot = ListEntry {
+            display_name: Some("Reboot".to_owned()),
+            exec_command: vec!["true".to_owned()],
+            exec_flags: RunFlags::new().with_confirm(true),
+            ..Default::default()
+        };
+        let asks =
+            |res: RunResult| matches!(res, RunResult::Status(msg) if msg.contains("confirm"));
+
+        state.search("reb", 10);
+        assert!(asks(state.run(&reboot)));
+        state.search("reb", 20);
+        assert!(!asks(state.run(&reboot)));
+
+        assert!(asks(state.run(&reboot)));
+        state.search("fire", 10);
+        state.search("reb", 10);
+        assert!(asks(state.run(&reboot)));
+        state.actions(&reboot);
+        assert!(asks(state.run(&reboot)));
+    }
+
+    #[cfg(feature = "plugin-lua")]
+    #[test]
+    fn reloads_in_the_background() {
+        use std::fs;
+
+        let plugin = |name: &str| {
+            format!(
+                "plugin {{ name = \"Reload\", entries = {{ entry {{ name = \"{}\", exec = \"true\" }} }} }}\n",
+                name
+            )
+        };
+        let (dir, conf) = lua_config(&plugin("before"), "allow = ['read_all']\n");
+        let file = conf.file.clone();
+        let gate = dir.join("gate");
+        let mut state = State::new(Config {
+            loaded_plugins: vec![LoadablePlugins::Lua(conf)],
+            ..Default::default()
+        });
+        state.start();
+        state.watch(ConfigSource::default());
+        assert_eq!(1, state.search("before", 10).len());
+
+        // The new version cannot finish starting until the gate exists, so
+        // `poll` only gets past the reload if it does not wait for it.
+        let waiting = format!(
+            "while not require('tmpas').fs.read({:?}) do end\n{}",
+            gate.display().to_string(),
+            plugin("after")
+        );
+        fs::write(&file, waiting).unwrap();
+        assert!(state.poll());
+        assert!(state.search("after", 10).is_empty());
+        fs::write(&gate, "").unwrap();
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(5) && !state.poll() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(state.search("before", 10).is_empty());
+        assert_eq!(1, state.search("after", 10).len());
+    }
+
+    #[cfg(feature = "plugin-lua")]
+    #[test]
+    fn yielding_plugins_load_when_idle() {
+        let (_dir, conf) = lua_config(
+            r#"
+            plugin {
+                name = "Yielding",
+                next = function()
+                    for i = 1, 3 do
+                        for _ = 1, 100 do
+                            coroutine.yield()
+                        end
+                        coroutine.yield(entry { name = "slow " .. i, exec = "true" })
+                    end
+                end,
+            }
+        "#,
+            "",
+        );
+        let mut state = State::new(Config {
+            loaded_plugins: vec![LoadablePlugins::Lua(conf)],
+            ..Default::default()
+        });
+        state.start();
+        assert!(state.search("slow", 10).is_empty());
+
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(5) && state.search("slow", 10).len() < 3 {
+            state.poll();
+        }
+        assert_eq!(3, state.search("slow", 10).len());
+        assert!(!state.poll());
+        assert!(!state.loading);
+    }
+}
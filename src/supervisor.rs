@@ -0,0 +1,443 @@
+//! Runs every plugin on a thread of its own, so that one that hangs or panics
+//! can be cut off while the rest keep working.
+
+use crate::config::Config;
+use crate::model::{EntryPlugin, ListEntry, Selected};
+
+use serde::{Deserialize, Serialize};
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::Once;
+use std::task::Poll;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What the names of plugin threads start with.
+const THREAD_PREFIX: &str = "plugin ";
+
+/// The `[plugin_budget]` block of the config: how long a plugin may take
+/// before it is disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginBudget {
+    /// For `start`, where most plugins do their loading. Plugins start in
+    /// parallel, so this is also roughly the longest startup can take.
+    pub start_ms: u64,
+    /// For producing each entry, and for answering queries and selections.
+    pub next_ms: u64,
+}
+
+impl Default for PluginBudget {
+    fn default() -> Self {
+        Self {
+            start_ms: 10_000,
+            next_ms: 2_000,
+        }
+    }
+}
+
+enum Request {
+    Start(Box<Config>),
+    Next,
+    Query(String),
+    Fallback(String),
+    Actions(ListEntry),
+    Select(ListEntry),
+}
+
+enum Response {
+    /// The plugin's name, and whether its queries may be cached.
+    Started(String, bool),
+    Entry(Poll<Option<ListEntry>>),
+    Entries(Vec<ListEntry>),
+    Selected(Option<Selected>),
+}
+
+/// The launcher's side of a plugin thread. Every call waits for the plugin
+/// for at most its budget; a plugin that runs over, panics or dies is
+/// disabled for the rest of the session. A thread stuck in a plugin cannot
+/// be killed, so it is left behind.
+pub struct Supervisor {
+    name: String,
+    budget: PluginBudget,
+    requests: Sender<Request>,
+    responses: Receiver<Result<Response, String>>,
+    /// When the plugin was asked to start, until it has answered.
+    starting: Option<Instant>,
+    /// Set once `next` has returned `None`.
+    exhausted: bool,
+    /// What the plugin's `caches_queries` said once it had started.
+    caches_queries: bool,
+    disabled: bool,
+    /// Why the plugin was disabled, until the UI has shown it.
+    warning: Option<String>,
+}
+
+impl Supervisor {
+    /// Spawns the plugin's thread and loads the plugin on it. `name` is used
+    /// until the plugin has started and can name itself.
+    pub fn spawn<F>(name: String, budget: PluginBudget, load: F) -> Self
+    where
+        F: FnOnce() -> Box<dyn EntryPlugin> + Send + 'static,
+    {
+        quiet_plugin_panics();
+        let (requests, request_rx) = mpsc::channel();
+        let (response_tx, responses) = mpsc::channel();
+        let res = thread::Builder::new()
+            .name(format!("{}{}", THREAD_PREFIX, name))
+            .spawn(move || serve(load, request_rx, response_tx));
+        let mut retvl = Self {
+            name,
+            budget,
+            requests,
+            responses,
+            starting: None,
+            exhausted: false,
+            caches_queries: true,
+            disabled: false,
+            warning: None,
+        };
+        if let Err(e) = res {
+            retvl.disable(format!("could not be run: {}", e));
+        }
+        retvl
+    }
+
+    /// Asks the plugin to start without waiting for it, so that all plugins
+    /// can start at once. `finish_start` waits for the answer; until then
+    /// the plugin counts as still working on its entries, and has nothing to
+    /// say to queries or selections.
+    pub fn begin_start(&mut self, config: &Config) {
+        self.send(Request::Start(Box::new(config.clone())));
+        self.starting = Some(Instant::now());
+    }
+
+    /// Waits for the plugin to finish starting, for what is left of its
+    /// budget.
+    pub fn finish_start(&mut self) {
+        if let Some(started) = self.starting.take() {
+            let budget = self.budget.start_ms;
+            let deadline = started + Duration::from_millis(budget);
+            let res = self.receive(deadline, budget, "to start");
+            self.started(res);
+        }
+    }
+
+    /// Whether the plugin has finished starting, collecting its answer if it
+    /// has one without waiting for it.
+    fn poll_start(&mut self) -> bool {
+        let started = match self.starting {
+            Some(started) => started,
+            None => {
+                return true;
+            }
+        };
+        let budget = self.budget.start_ms;
+        let res = match self.responses.try_recv() {
+            Ok(res) => Ok(res),
+            Err(TryRecvError::Empty) if started.elapsed() < Duration::from_millis(budget) => {
+                return false;
+            }
+            Err(TryRecvError::Empty) => Err(RecvTimeoutError::Timeout),
+            Err(TryRecvError::Disconnected) => Err(RecvTimeoutError::Disconnected),
+        };
+        self.starting = None;
+        let res = self.answer(res, budget, "to start");
+        self.started(res);
+        true
+    }
+
+    fn started(&mut self, res: Option<Response>) {
+        if let Some(Response::Started(name, caches_queries)) = res {
+            self.name = name;
+            self.caches_queries = caches_queries;
+        }
+    }
+
+    /// The plugin's next entry, or `Poll::Pending` if it is still working on
+    /// one. Each call gets a budget of its own.
+    pub fn next(&mut self) -> Poll<Option<ListEntry>> {
+        if self.exhausted {
+            return Poll::Ready(None);
+        }
+        if !self.poll_start() {
+            return Poll::Pending;
+        }
+        match self.call(Request::Next, "to load an entry") {
+            Some(Response::Entry(Poll::Ready(Some(entry)))) => Poll::Ready(Some(entry)),
+            Some(Response::Entry(Poll::Pending)) => Poll::Pending,
+            _ => {
+                self.exhausted = true;
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    pub fn query(&mut self, text: &str) -> Vec<ListEntry> {
+        self.call_entries(Request::Query(text.to_owned()), "to answer a query")
+    }
+
+    pub fn caches_queries(&self) -> bool {
+        self.caches_queries
+    }
+
+    pub fn fallback(&mut self, text: &str) -> Vec<ListEntry> {
+        self.call_entries(Request::Fallback(text.to_owned()), "to answer a query")
+    }
+
+    pub fn actions(&mut self, entry: &ListEntry) -> Vec<ListEntry> {
+        self.call_entries(Request::Actions(entry.clone()), "to list actions")
+    }
+
+    pub fn select(&mut self, entry: &ListEntry) -> Option<Selected> {
+        match self.call(Request::Select(entry.clone()), "to run an entry") {
+            Some(Response::Selected(selected)) => selected,
+            _ => None,
+        }
+    }
+
+    /// Why the plugin was disabled, once.
+    pub fn take_warning(&mut self) -> Option<String> {
+        self.warning.take()
+    }
+
+    fn call_entries(&mut self, request: Request, what: &str) -> Vec<ListEntry> {
+        match self.call(request, what) {
+            Some(Response::Entries(entries)) => entries,
+            _ => Vec::new(),
+        }
+    }
+
+    fn call(&mut self, request: Request, what: &str) -> Option<Response> {
+        if !self.poll_start() {
+            return None;
+        }
+        self.send(request);
+        let budget = self.budget.next_ms;
+        let deadline = Instant::now() + Duration::from_millis(budget);
+        self.receive(deadline, budget, what)
+    }
+
+    fn send(&mut self, request: Request) {
+        if !self.disabled && self.requests.send(request).is_err() {
+            self.disable("stopped unexpectedly".to_owned());
+        }
+    }
+
+    /// Waits for the answer to the last request. `what` describes the
+    /// request for the warning, like "to start".
+    fn receive(&mut self, deadline: Instant, budget_ms: u64, what: &str) -> Option<Response> {
+        if self.disabled {
+            return None;
+        }
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let res = self.responses.recv_timeout(timeout);
+        self.answer(res, budget_ms, what)
+    }
+
+    /// Turns what came back from the plugin thread into its answer,
+    /// disabling the plugin if there was none.
+    fn answer(
+        &mut self,
+        res: Result<Result<Response, String>, RecvTimeoutError>,
+        budget_ms: u64,
+        what: &str,
+    ) -> Option<Response> {
+        if self.disabled {
+            return None;
+        }
+        match res {
+            Ok(Ok(response)) => Some(response),
+            Ok(Err(msg)) => {
+                self.disable(format!("panicked trying {}: {}", what, msg));
+                None
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                self.disable(format!("took longer than {} ms {}", budget_ms, what));
+                None
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                self.disable("stopped unexpectedly".to_owned());
+                None
+            }
+        }
+    }
+
+    fn disable(&mut self, reason: String) {
+        let msg = format!("Disabled plugin {}: it {}", self.name, reason);
+        eprintln!("WARNING: {}", msg);
+        self.disabled = true;
+        self.warning = Some(msg);
+    }
+}
+
+/// Keeps the panic hook from printing panics on plugin threads, which the
+/// supervisor reports in its warning anyway. With `RUST_BACKTRACE` set,
+/// printing one can take longer than the plugin's budget.
+fn quiet_plugin_panics() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let default = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let on_plugin = thread::current()
+                .name()
+                .is_some_and(|name| name.starts_with(THREAD_PREFIX));
+            if !on_plugin {
+                default(info);
+            }
+        }));
+    });
+}
+
+/// The plugin thread: loads the plugin, then answers requests until the
+/// launcher goes away or the plugin panics.
+fn serve<F>(load: F, requests: Receiver<Request>, responses: Sender<Result<Response, String>>)
+where
+    F: FnOnce() -> Box<dyn EntryPlugin>,
+{
+    let mut plugin = match panic::catch_unwind(AssertUnwindSafe(load)) {
+        Ok(plugin) => plugin,
+        Err(payload) => {
+            let _ = responses.send(Err(panic_message(payload)));
+            return;
+        }
+    };
+    for request in requests {
+        let res = panic::catch_unwind(AssertUnwindSafe(|| handle(plugin.as_mut(), request)));
+        let panicked = res.is_err();
+        if responses.send(res.map_err(panic_message)).is_err() || panicked {
+            return;
+        }
+    }
+}
+
+fn handle(plugin: &mut dyn EntryPlugin, request: Request) -> Response {
+    match request {
+        Request::Start(config) => {
+            plugin.start(&config);
+            Response::Started(plugin.name(), plugin.caches_queries())
+        }
+        Request::Next => Response::Entry(plugin.poll_next()),
+        Request::Query(text) => Response::Entries(plugin.query(&text)),
+        Request::Fallback(text) => Response::Entries(plugin.fallback(&text)),
+        Request::Actions(entry) => Response::Entries(plugin.actions(&entry)),
+        Request::Select(entry) => Response::Selected(plugin.select(&entry)),
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        (*msg).to_owned()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown error".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread::sleep;
+
+    /// Counts to three, taking a nap or panicking on the way if asked to.
+    /// Has nothing ready every other time it is polled, and wants its
+    /// queries asked afresh every time.
+    struct Counter {
+        count: usize,
+        nap_at: Option<usize>,
+        panic_at: Option<usize>,
+        ready: bool,
+    }
+
+    impl EntryPlugin for Counter {
+        fn name(&self) -> String {
+            "Counter".to_owned()
+        }
+        fn start(&mut self, _config: &Config) {}
+        fn next(&mut self) -> Option<ListEntry> {
+            self.count += 1;
+            if self.nap_at == Some(self.count) {
+                sleep(Duration::from_millis(500));
+            }
+            if self.panic_at == Some(self.count) {
+                panic!("counted too far");
+            }
+            Some(self.count)
+                .filter(|count| *count <= 3)
+                .map(|count| ListEntry {
+                    display_name: Some(count.to_string()),
+                    ..Default::default()
+                })
+        }
+        fn poll_next(&mut self) -> Poll<Option<ListEntry>> {
+            self.ready = !self.ready;
+            if self.ready {
+                Poll::Ready(self.next())
+            } else {
+                Poll::Pending
+            }
+        }
+        fn caches_queries(&self) -> bool {
+            false
+        }
+    }
+
+    fn spawn(nap_at: Option<usize>, panic_at: Option<usize>) -> Supervisor {
+        let budget = PluginBudget {
+            start_ms: 1000,
+            next_ms: 100,
+        };
+        let mut retvl = Supervisor::spawn("counter".to_owned(), budget, move || {
+            Box::new(Counter {
+                count: 0,
+                nap_at,
+                panic_at,
+                ready: false,
+            })
+        });
+        retvl.begin_start(&Config::default());
+        retvl.finish_start();
+        retvl
+    }
+
+    fn drain(plugin: &mut Supervisor) -> Vec<String> {
+        let mut retvl = Vec::new();
+        loop {
+            match plugin.next() {
+                Poll::Ready(Some(ent)) => retvl.push(ent.name().to_owned()),
+                Poll::Ready(None) => return retvl,
+                Poll::Pending => {}
+            }
+        }
+    }
+
+    #[test]
+    fn budgets_and_panics() {
+        let mut healthy = spawn(None, None);
+        assert_eq!("Counter", healthy.name);
+        assert!(!healthy.caches_queries());
+        assert_eq!(vec!["1", "2", "3"], drain(&mut healthy));
+        assert_eq!(None, healthy.take_warning());
+
+        let mut slow = spawn(Some(2), None);
+        assert_eq!(vec!["1"], drain(&mut slow));
+        let warning = slow.take_warning().unwrap();
+        assert_eq!(
+            "Disabled plugin Counter: it took longer than 100 ms to load an entry",
+            warning
+        );
+        assert!(slow.query("anything").is_empty());
+
+        let mut broken = spawn(None, Some(3));
+        assert_eq!(vec!["1", "2"], drain(&mut broken));
+        assert_eq!(
+            Some("Disabled plugin Counter: it panicked trying to load an entry: counted too far"),
+            broken.take_warning().as_deref()
+        );
+        assert_eq!(None, broken.take_warning());
+    }
+}
@@ -5,6 +5,7 @@ use resultslist::EntryList;
 use searchbar::SearchBuffer;
 mod resultslist;
 
+use crate::state::{RunResult, POLL_INTERVAL};
 use crate::State;
 use crate::{AppMessage, UiMessage};
 
@@ -12,7 +13,7 @@ use std::io::{self, Write};
 
 use crossterm::event;
 use crossterm::terminal::{self, ClearType};
-use crossterm::{cursor, ExecutableCommand, QueueableCommand};
+use crossterm::{cursor, style, ExecutableCommand, QueueableCommand};
 use io::Stdout;
 
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
@@ -21,18 +22,58 @@ pub fn run(mut state: State) {
     let mut ui = UiState::new().unwrap();
     let (_width, height) = terminal::size().unwrap();
     ui.send_message(AppMessage::SearchResults(state.search("", height.into())));
+    let mut needs_redraw = true;
     loop {
-        let step_res = ui.display().and_then(|_| ui.step());
-        match step_res {
+        if let Some(warning) = state.take_warning() {
+            ui.send_message(AppMessage::Status(warning));
+            needs_redraw = true;
+        }
+        if needs_redraw {
+            if let Err(e) = ui.display() {
+                panic!("Got error: {:?}", e);
+            }
+        }
+        needs_redraw = true;
+        match ui.step() {
+            Ok(Some(UiMessage::Idle)) => {
+                if state.poll() && !ui.showing_actions {
+                    let (_width, height) = terminal::size().unwrap();
+                    let res = state.search(&ui.search_buffer.buffer, height.into());
+                    ui.send_message(AppMessage::SearchResults(res));
+                } else {
+                    needs_redraw = false;
+                }
+            }
             Ok(Some(UiMessage::DoSearch(key))) => {
                 let (_width, height) = terminal::size().unwrap();
                 let res = state.search(&key, height.into());
                 ui.send_message(AppMessage::SearchResults(res));
             }
-            Ok(Some(UiMessage::RunEntry(ent))) => {
-                drop(ui);
-                state.run(&ent);
-                return;
+            Ok(Some(UiMessage::RunEntry(ent))) => match state.run(&ent) {
+                RunResult::Nothing => {}
+                RunResult::Status(msg) => {
+                    ui.send_message(AppMessage::Status(msg));
+                }
+                RunResult::Entries(entries) => {
+                    ui.send_message(AppMessage::Actions(entries));
+                    ui.send_message(AppMessage::Status(format!(
+                        "{} (Esc to go back)",
+                        ent.name()
+                    )));
+                }
+                RunResult::Exec(ent) => {
+                    drop(ui);
+                    state.exec(&ent);
+                    return;
+                }
+            },
+            Ok(Some(UiMessage::ShowActions(ent))) => {
+                let actions = state.actions(&ent);
+                ui.send_message(AppMessage::Actions(actions));
+                ui.send_message(AppMessage::Status(format!(
+                    "Actions for {} (Esc to go back)",
+                    ent.name()
+                )));
             }
             Ok(Some(UiMessage::Quit)) => {
                 return;
@@ -49,6 +90,8 @@ pub struct UiState {
     stdout: LazyWriter<Stdout>,
     results_list: EntryList,
     search_buffer: SearchBuffer,
+    status: Option<String>,
+    showing_actions: bool,
 }
 
 impl UiState {
@@ -65,10 +108,15 @@ impl UiState {
             stdout: LazyWriter::new(stdout),
             search_buffer: SearchBuffer::new(),
             results_list: EntryList::new(),
+            status: None,
+            showing_actions: false,
         })
     }
 
     pub fn step(&mut self) -> crossterm::Result<Option<UiMessage>> {
+        if !event::poll(POLL_INTERVAL)? {
+            return Ok(Some(UiMessage::Idle));
+        }
         let key_event = match event::read()? {
             Event::Key(KeyEvent {
                 code: KeyCode::Char('c'),
@@ -76,11 +124,27 @@ impl UiState {
             }) => {
                 return Ok(Some(UiMessage::Quit));
             }
+            Event::Key(KeyEvent {
+                code: KeyCode::Enter,
+                modifiers: KeyModifiers::SHIFT,
+            })
+            | Event::Key(KeyEvent {
+                code: KeyCode::Tab, ..
+            }) => {
+                self.status = None;
+                let result = self
+                    .results_list
+                    .selected()
+                    .cloned()
+                    .map(UiMessage::ShowActions);
+                return Ok(result);
+            }
             Event::Key(KeyEvent { code, .. }) => code,
             _other => {
                 return Ok(None);
             }
         };
+        self.status = None;
         match key_event {
             KeyCode::Enter => {
                 let result = self
@@ -90,6 +154,10 @@ impl UiState {
                     .map(UiMessage::RunEntry);
                 Ok(result)
             }
+            KeyCode::Esc if self.showing_actions => {
+                self.showing_actions = false;
+                Ok(Some(UiMessage::DoSearch(self.search_buffer.buffer.clone())))
+            }
             KeyCode::Backspace => {
                 self.search_buffer.backspace();
                 Ok(Some(UiMessage::DoSearch(self.search_buffer.buffer.clone())))
@@ -126,6 +194,14 @@ impl UiState {
         match app_msg {
             AppMessage::SearchResults(res) => {
                 self.results_list.set_results(res);
+                self.showing_actions = false;
+            }
+            AppMessage::Actions(actions) => {
+                self.results_list.set_results(actions);
+                self.showing_actions = true;
+            }
+            AppMessage::Status(msg) => {
+                self.status = Some(msg);
             }
         }
     }
@@ -135,6 +211,12 @@ impl UiState {
             .queue(terminal::Clear(ClearType::All))?
             .queue(cursor::MoveTo(0, self.search_buffer.height()))?;
         self.results_list.display(&mut self.stdout)?;
+        if let Some(status) = self.status.as_deref() {
+            let (_width, height) = terminal::size()?;
+            self.stdout
+                .queue(cursor::MoveTo(0, height.saturating_sub(1)))?
+                .queue(style::Print(status))?;
+        }
         self.search_buffer.display(&mut self.stdout)?;
         self.stdout.flush()?;
         Ok(())
@@ -1,3 +1,6 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
 pub fn filter_log<T, E, F: Fn(E)>(err_cb: F) -> impl Fn(Result<T, E>) -> Option<T> {
     move |res| match res {
         Ok(r) => Some(r),
@@ -54,3 +57,139 @@ where
         }
     }
 }
+
+/// Resolves a binary name the same way `execvp` would, returning the full path
+/// of the first match in `$PATH`.
+pub fn find_in_path(binary: &str) -> Option<PathBuf> {
+    if binary.contains('/') {
+        return Some(PathBuf::from(binary));
+    }
+    let raw_path = env::var_os("PATH")?;
+    env::split_paths(&raw_path)
+        .map(|dir| dir.join(binary))
+        .find(|candidate| Path::is_file(candidate))
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME").map(PathBuf::from)
+}
+
+/// Expands a leading `~` into the user's home directory.
+pub fn expand_tilde(raw: &str) -> PathBuf {
+    let rest = match raw.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+        _ => {
+            return PathBuf::from(raw);
+        }
+    };
+    match home_dir() {
+        Some(home) => home.join(rest.trim_start_matches('/')),
+        None => PathBuf::from(raw),
+    }
+}
+
+/// The inverse of [`expand_tilde`], used to keep displayed paths short.
+pub fn contract_tilde(path: &Path) -> String {
+    let stripped = home_dir().and_then(|home| {
+        let rest = path.strip_prefix(home).ok()?;
+        Some(Path::new("~").join(rest))
+    });
+    stripped.as_deref().unwrap_or(path).display().to_string()
+}
+
+/// Formats a byte count with binary units, like `1.5 GiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS.iter() {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+    if unit == "B" {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, unit)
+    }
+}
+
+/// Joins an argv into a command line a POSIX shell splits back into the
+/// same arguments.
+pub fn shell_quote(args: &[String]) -> String {
+    let quote = |arg: &String| {
+        let plain = !arg.is_empty()
+            && arg
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%^".contains(c));
+        if plain {
+            arg.clone()
+        } else {
+            format!("'{}'", arg.replace('\'', "'\\''"))
+        }
+    };
+    args.iter().map(quote).collect::<Vec<_>>().join(" ")
+}
+
+/// A directory for a test's files, removed again when the test ends, even if
+/// it panics.
+#[cfg(test)]
+pub struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    /// Creates an empty directory named after `name` and the process.
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("tmpas-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempDir {
+    type Target = Path;
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!("512 B", format_size(512));
+        assert_eq!("200.0 MiB", format_size(200 * 1024 * 1024));
+    }
+
+    #[test]
+    fn quoting() {
+        let args: Vec<_> = ["retroarch", "-L", "~/roms/Kirby's Dream Land.gb", ""]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert_eq!(
+            r#"retroarch -L '~/roms/Kirby'\''s Dream Land.gb' ''"#,
+            shell_quote(&args)
+        );
+    }
+}
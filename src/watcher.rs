@@ -0,0 +1,128 @@
+//! Notices when files change on disk. The directories holding the files are
+//! watched rather than the files themselves, since most editors save by
+//! writing a new file and renaming it over the old one. A symlinked file is
+//! edited at its target, so the target's directory is watched as well.
+
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor};
+use nix::unistd::close;
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+pub struct FileWatcher {
+    inotify: Inotify,
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+    /// Every path whose change counts, mapped to the watched file it counts
+    /// for: each file itself, and its target if it is a symlink.
+    files: HashMap<PathBuf, PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn new() -> nix::Result<Self> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+        Ok(Self {
+            inotify,
+            dirs: HashMap::new(),
+            files: HashMap::new(),
+        })
+    }
+
+    /// Replaces the set of watched files. Files that do not exist yet are
+    /// noticed once they are created, as long as their directory exists.
+    pub fn watch<I: IntoIterator<Item = PathBuf>>(&mut self, files: I) {
+        self.files = HashMap::new();
+        for file in files.into_iter().map(|file| normalize(&file)) {
+            if let Ok(target) = file.canonicalize() {
+                self.files.insert(target, file.clone());
+            }
+            self.files.insert(file.clone(), file);
+        }
+        let watched: HashSet<_> = self.dirs.values().cloned().collect();
+        let dirs: HashSet<_> = self.files.keys().filter_map(|file| file.parent()).collect();
+        for dir in dirs.into_iter().filter(|dir| !watched.contains(*dir)) {
+            let flags = AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_MOVED_TO;
+            match self.inotify.add_watch(dir, flags) {
+                Ok(wd) => {
+                    self.dirs.insert(wd, dir.to_owned());
+                }
+                Err(e) => {
+                    eprintln!("WARNING: Cannot watch {} for changes: {}", dir.display(), e);
+                }
+            }
+        }
+    }
+
+    /// The watched files that have changed since the last call, as given by
+    /// `normalize`.
+    pub fn changed(&mut self) -> HashSet<PathBuf> {
+        let mut retvl = HashSet::new();
+        // Without any pending events the read fails with EAGAIN.
+        while let Ok(events) = self.inotify.read_events() {
+            for event in events {
+                let dir = self.dirs.get(&event.wd);
+                let path = dir.zip(event.name).map(|(dir, name)| dir.join(name));
+                if let Some(file) = path.and_then(|path| self.files.get(&path)) {
+                    retvl.insert(file.clone());
+                }
+            }
+        }
+        retvl
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        let _ = close(self.inotify.as_raw_fd());
+    }
+}
+
+/// Makes a path absolute with its directory's symlinks resolved, so that the
+/// same file always ends up as the same path.
+pub fn normalize(path: &Path) -> PathBuf {
+    let path = match env::current_dir() {
+        Ok(cwd) => cwd.join(path),
+        Err(_) => path.to_owned(),
+    };
+    let dir = path.parent().and_then(|dir| dir.canonicalize().ok());
+    match (dir, path.file_name()) {
+        (Some(dir), Some(name)) => dir.join(name),
+        _ => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::TempDir;
+
+    use std::fs;
+
+    #[test]
+    fn replaced_and_written() {
+        let dir = TempDir::new("watch");
+        let dotfiles = TempDir::new("watch-dotfiles");
+        let config = dir.join("config.toml");
+        let plugin = dir.join("plugin.lua");
+        let linked = dir.join("linked.lua");
+        fs::write(&config, "").unwrap();
+        fs::write(dotfiles.join("linked.lua"), "").unwrap();
+        std::os::unix::fs::symlink(dotfiles.join("linked.lua"), &linked).unwrap();
+        let mut watcher = FileWatcher::new().unwrap();
+        watcher.watch(vec![config.clone(), plugin.clone(), linked.clone()]);
+        assert!(watcher.changed().is_empty());
+
+        fs::write(dir.join("other.txt"), "ignored").unwrap();
+        fs::write(&config, "keep_open = true").unwrap();
+        fs::write(dir.join(".plugin.lua.swp"), "return 1").unwrap();
+        fs::rename(dir.join(".plugin.lua.swp"), &plugin).unwrap();
+        fs::write(dotfiles.join("linked.lua"), "return 2").unwrap();
+        let changed = watcher.changed();
+
+        let expected: HashSet<_> = vec![normalize(&config), normalize(&plugin), normalize(&linked)]
+            .into_iter()
+            .collect();
+        assert_eq!(expected, changed);
+    }
+}